@@ -1,12 +1,1231 @@
 // Copyright 2020-2021 postUI Lab. All rights reserved. MIT license.
 
+use super::validator::INVALID_CSS;
+use crate::resolve::CSSTemplate;
+use std::{fmt, iter, rc::Rc};
+use swc_common::{errors::DiagnosticId, BytePos, LineCol, SourceMap, Span, Spanned, DUMMY_SP};
 use swc_ecma_ast::*;
+use swc_ecma_utils::{quote_str, HANDLER};
+use swc_ecmascript::codegen::{text_writer::JsWriter, Emitter, Node};
+
+/// The placeholder of the template expressions in the CSS source.
+const PLACEHOLDER: char = '\u{0}';
+
+/// A stylesheet parsed from the `$style` template.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CSS {
+  pub span: Span,
+  pub rules: Vec<Rule>,
+  /// the expressions of the template, referred by `ValuePart::Expr(index)`
+  pub exprs: Vec<Expr>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Rule {
+  Style(StyleRule), // match `p { color: red }`
+  At(AtRule),       // match `@media screen { ... }`
+  Comment(String),  // the rule that is replaced by a comment, like `/* unused h1 */`
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StyleRule {
+  pub span: Span,
+  pub selectors: Vec<Selector>,
+  pub declarations: Vec<Declaration>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AtRule {
+  pub span: Span,
+  pub name: String,
+  pub prelude: Vec<ValuePart>,
+  pub body: AtRuleBody,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AtRuleBody {
+  None,                           // match `@import "./a.css";`
+  Rules(Vec<Rule>),               // match `@media` and `@supports`
+  Declarations(Vec<Declaration>), // match `@font-face` and `@page`
+  Keyframes(Vec<Keyframe>),       // match `@keyframes`
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Keyframe {
+  pub selectors: Vec<String>, // match `from`, `to` and `50%`
+  pub declarations: Vec<Declaration>,
+}
 
 #[derive(Clone, Debug, Eq, PartialEq)]
-pub struct CSS {}
+pub struct Declaration {
+  pub property: String,
+  pub value: Vec<ValuePart>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ValuePart {
+  Text(String),
+  Expr(usize), // the index of `CSS.exprs`
+}
+
+/// The static shape of an element in the templates, used to match the selectors.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ElementShape {
+  pub tag: String,
+  pub id: Option<String>,
+  pub classes: Vec<String>,
+  pub attrs: Vec<String>,
+  /// the element has a spread or a dynamic `className`/`id` attribute
+  pub is_dynamic: bool,
+}
+
+/// A complex selector like `ul > li.active a:hover`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Selector {
+  pub compounds: Vec<CompoundSelector>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Combinator {
+  Descendant,        // match `a b`
+  Child,             // match `a > b`
+  NextSibling,       // match `a + b`
+  SubsequentSibling, // match `a ~ b`
+}
+
+/// A compound selector like `li.active:hover`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CompoundSelector {
+  /// the combinator to the previous compound selector
+  pub combinator: Option<Combinator>,
+  pub tag: Option<String>,
+  pub id: Option<String>,
+  pub classes: Vec<String>,
+  pub attrs: Vec<String>,
+  pub pseudo_classes: Vec<String>,
+  pub pseudo_element: Option<String>,
+  /// the inner selector of `:global(...)`
+  pub global: Option<String>,
+}
 
 impl CSS {
+  /// Parse the `$style` template, parse errors are emitted by the `HANDLER`.
   pub fn parse(tpl: &Tpl) -> Self {
-    CSS {}
+    let mut source = String::new();
+    // the source positions of the chars, used to create the spans of the rules
+    let mut positions: Vec<BytePos> = vec![];
+    for (i, quasi) in tpl.quasis.iter().enumerate() {
+      let raw = quasi.raw.value.as_ref();
+      source.push_str(raw);
+      for (idx, _) in raw.char_indices() {
+        positions.push(quasi.span.lo + BytePos(idx as u32));
+      }
+      if i < tpl.exprs.len() {
+        let placeholder = format!("{}{}{}", PLACEHOLDER, i, PLACEHOLDER);
+        source.push_str(&placeholder);
+        for _ in placeholder.chars() {
+          positions.push(tpl.exprs[i].span().lo);
+        }
+      }
+    }
+    let rules = match parse_rules(&source, positions) {
+      Ok(rules) => rules,
+      Err((message, span)) => {
+        // the errors at the end of the template are located at the whole template
+        let span = if span.is_dummy() { tpl.span } else { span };
+        HANDLER.with(|handler| {
+          handler
+            .struct_span_err(span, &message)
+            .code(DiagnosticId::Error(INVALID_CSS.into()))
+            .emit()
+        });
+        vec![]
+      }
+    };
+    CSS {
+      span: tpl.span,
+      rules,
+      exprs: tpl.exprs.iter().map(|expr| expr.as_ref().clone()).collect(),
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.rules.is_empty()
+  }
+
+  /// Scope the stylesheet with the class name, the rules that can't match any of the elements
+  /// are replaced by comments and the selectors that can't match are dropped from the rules.
+  /// Returns the compound selectors of the used rules, and the selectors of the unused rules
+  /// and the dropped selectors with the spans of their rules.
+  pub fn scope(
+    &mut self,
+    class_name: &str,
+    elements: &[ElementShape],
+  ) -> (Vec<CompoundSelector>, Vec<(String, Span)>) {
+    let mut compounds: Vec<CompoundSelector> = vec![];
+    let mut unused: Vec<(String, Span)> = vec![];
+    scope_rules(
+      &mut self.rules,
+      class_name,
+      elements,
+      &mut compounds,
+      &mut unused,
+    );
+    (compounds, unused)
+  }
+
+  /// Strip the comments of the unused rules, and the at-rules that become empty.
+  pub fn strip_unused(&mut self) {
+    strip_rules(&mut self.rules);
+  }
+
+  /// Replace the expressions in the declaration values with the custom properties
+  /// `var(--{prefix}-{index})` if `should_bind(index)` returns true, the expressions in the
+  /// at-rule preludes are kept since they can't use custom properties.
+  /// Returns the indexes of the bound expressions.
+  pub fn bind_vars(&mut self, prefix: &str, should_bind: impl Fn(usize) -> bool) -> Vec<usize> {
+    let mut bound: Vec<usize> = vec![];
+    bind_rules(&mut self.rules, prefix, &should_bind, &mut bound);
+    bound
+  }
+
+  /// Print the stylesheet to the CSS template with the JS code of the expressions.
+  pub fn to_template(&self) -> CSSTemplate {
+    let (quasis, exprs) = self.print();
+    CSSTemplate {
+      quasis,
+      exprs: exprs
+        .into_iter()
+        .map(|idx| print_expr(&self.exprs[idx]))
+        .collect(),
+    }
+  }
+
+  /// Print the stylesheet to a template literal.
+  pub fn to_tpl(&self) -> Tpl {
+    let (quasis, exprs) = self.print();
+    let len = quasis.len();
+    Tpl {
+      span: DUMMY_SP,
+      exprs: exprs
+        .into_iter()
+        .map(|idx| Box::new(self.exprs[idx].clone()))
+        .collect(),
+      quasis: quasis
+        .into_iter()
+        .enumerate()
+        .map(|(i, quasi)| TplElement {
+          span: DUMMY_SP,
+          tail: i == len - 1,
+          cooked: None,
+          raw: quote_str!(quasi),
+        })
+        .collect(),
+    }
+  }
+
+  /// Print the stylesheet to CSS with the mappings to the source, the expressions are ignored
+  /// so it should be used for the static stylesheet only.
+  pub fn to_css(&self) -> (String, Vec<(BytePos, LineCol)>) {
+    let mut printer = Printer::default();
+    for rule in &self.rules {
+      printer.print_rule(rule, 0);
+    }
+    let Printer {
+      quasis,
+      text,
+      mappings,
+      ..
+    } = printer;
+    (quasis.concat() + &text, mappings)
+  }
+
+  fn print(&self) -> (Vec<String>, Vec<usize>) {
+    let mut printer = Printer::default();
+    for rule in &self.rules {
+      printer.print_rule(rule, 0);
+    }
+    let Printer {
+      mut quasis,
+      text,
+      exprs,
+      ..
+    } = printer;
+    quasis.push(text);
+    (quasis, exprs)
+  }
+}
+
+impl CompoundSelector {
+  /// Check whether the compound selector can match the element, pseudo classes are ignored.
+  pub fn matches(&self, el: &ElementShape) -> bool {
+    if let Some(tag) = &self.tag {
+      if tag != "*" && !tag.eq_ignore_ascii_case(&el.tag) {
+        return false;
+      }
+    }
+    if el.is_dynamic {
+      return true;
+    }
+    if let Some(id) = &self.id {
+      if el.id.as_ref() != Some(id) {
+        return false;
+      }
+    }
+    self.classes.iter().all(|class| el.classes.contains(class))
+      && self.attrs.iter().all(|attr| {
+        let name = attr.split(|c| "~|^$*=".contains(c)).next().unwrap().trim();
+        el.attrs.iter().any(|a| a == name)
+      })
+  }
+}
+
+fn scope_rules(
+  rules: &mut [Rule],
+  class_name: &str,
+  elements: &[ElementShape],
+  compounds: &mut Vec<CompoundSelector>,
+  unused: &mut Vec<(String, Span)>,
+) {
+  for rule in rules.iter_mut() {
+    match rule {
+      Rule::Style(StyleRule {
+        span, selectors, ..
+      }) => {
+        let span = *span;
+        let is_used = |selector: &Selector| {
+          selector
+            .compounds
+            .iter()
+            .filter(|compound| compound.global.is_none())
+            .all(|compound| elements.iter().any(|el| compound.matches(el)))
+        };
+        if !selectors.iter().any(is_used) {
+          let selectors: Vec<String> = selectors.iter().map(|s| s.to_string()).collect();
+          let selectors = selectors.join(", ");
+          *rule = Rule::Comment(format!("unused {}", selectors));
+          unused.push((selectors, span));
+          continue;
+        }
+        // the selectors that can't match any element are dropped from the list
+        for selector in selectors.iter().filter(|s| !is_used(s)) {
+          unused.push((selector.to_string(), span));
+        }
+        selectors.retain(is_used);
+        for selector in selectors.iter_mut() {
+          for compound in selector.compounds.iter_mut() {
+            if compound.global.is_none() {
+              if !compounds.contains(compound) {
+                compounds.push(compound.clone());
+              }
+              compound.classes.push(class_name.into());
+            }
+          }
+        }
+      }
+      Rule::At(AtRule {
+        body: AtRuleBody::Rules(rules),
+        ..
+      }) => scope_rules(rules, class_name, elements, compounds, unused),
+      _ => {}
+    }
+  }
+}
+
+impl fmt::Display for Selector {
+  fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for compound in &self.compounds {
+      match compound.combinator {
+        Some(Combinator::Descendant) => write!(fmt, " ")?,
+        Some(Combinator::Child) => write!(fmt, " > ")?,
+        Some(Combinator::NextSibling) => write!(fmt, " + ")?,
+        Some(Combinator::SubsequentSibling) => write!(fmt, " ~ ")?,
+        None => {}
+      }
+      write!(fmt, "{}", compound)?;
+    }
+    Ok(())
+  }
+}
+
+impl fmt::Display for CompoundSelector {
+  fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if let Some(global) = &self.global {
+      return write!(fmt, "{}", global);
+    }
+    if let Some(tag) = &self.tag {
+      write!(fmt, "{}", tag)?;
+    }
+    if let Some(id) = &self.id {
+      write!(fmt, "#{}", id)?;
+    }
+    for class in &self.classes {
+      write!(fmt, ".{}", class)?;
+    }
+    for attr in &self.attrs {
+      write!(fmt, "[{}]", attr)?;
+    }
+    for pseudo_class in &self.pseudo_classes {
+      write!(fmt, ":{}", pseudo_class)?;
+    }
+    if let Some(pseudo_element) = &self.pseudo_element {
+      write!(fmt, "::{}", pseudo_element)?;
+    }
+    Ok(())
+  }
+}
+
+fn strip_rules(rules: &mut Vec<Rule>) {
+  rules.retain(|rule| !matches!(rule, Rule::Comment(_)));
+  for rule in rules.iter_mut() {
+    if let Rule::At(AtRule {
+      body: AtRuleBody::Rules(rules),
+      ..
+    }) = rule
+    {
+      strip_rules(rules);
+    }
+  }
+  rules.retain(|rule| {
+    !matches!(rule, Rule::At(AtRule {
+      body: AtRuleBody::Rules(rules),
+      ..
+    }) if rules.is_empty())
+  });
+}
+
+fn bind_rules(
+  rules: &mut [Rule],
+  prefix: &str,
+  should_bind: &dyn Fn(usize) -> bool,
+  bound: &mut Vec<usize>,
+) {
+  for rule in rules.iter_mut() {
+    match rule {
+      Rule::Style(StyleRule { declarations, .. }) => {
+        bind_declarations(declarations, prefix, should_bind, bound)
+      }
+      Rule::At(AtRule { body, .. }) => match body {
+        AtRuleBody::Rules(rules) => bind_rules(rules, prefix, should_bind, bound),
+        AtRuleBody::Declarations(declarations) => {
+          bind_declarations(declarations, prefix, should_bind, bound)
+        }
+        AtRuleBody::Keyframes(keyframes) => {
+          for Keyframe { declarations, .. } in keyframes.iter_mut() {
+            bind_declarations(declarations, prefix, should_bind, bound)
+          }
+        }
+        AtRuleBody::None => {}
+      },
+      Rule::Comment(_) => {}
+    }
+  }
+}
+
+fn bind_declarations(
+  declarations: &mut [Declaration],
+  prefix: &str,
+  should_bind: &dyn Fn(usize) -> bool,
+  bound: &mut Vec<usize>,
+) {
+  for Declaration { value, .. } in declarations.iter_mut() {
+    for part in value.iter_mut() {
+      if let ValuePart::Expr(idx) = *part {
+        if should_bind(idx) {
+          if !bound.contains(&idx) {
+            bound.push(idx);
+          }
+          *part = ValuePart::Text(format!("var(--{}-{})", prefix, idx));
+        }
+      }
+    }
+  }
+}
+
+#[derive(Default)]
+struct Printer {
+  quasis: Vec<String>,
+  text: String,
+  exprs: Vec<usize>,
+  mappings: Vec<(BytePos, LineCol)>,
+}
+
+impl Printer {
+  fn add_mapping(&mut self, span: Span, indent: usize) {
+    if span.is_dummy() {
+      return;
+    }
+    let line = self
+      .quasis
+      .iter()
+      .chain(iter::once(&self.text))
+      .map(|s| s.matches('\n').count())
+      .sum::<usize>();
+    self.mappings.push((
+      span.lo,
+      LineCol {
+        line: line as u32,
+        col: (indent * 2) as u32,
+      },
+    ));
+  }
+
+  fn print_rule(&mut self, rule: &Rule, indent: usize) {
+    let pad = "  ".repeat(indent);
+    match rule {
+      Rule::Comment(comment) => self.text.push_str(&format!("{}/* {} */\n", pad, comment)),
+      Rule::Style(StyleRule {
+        span,
+        selectors,
+        declarations,
+      }) => {
+        self.add_mapping(*span, indent);
+        let selectors: Vec<String> = selectors.iter().map(|s| s.to_string()).collect();
+        self
+          .text
+          .push_str(&format!("{}{} {{\n", pad, selectors.join(", ")));
+        self.print_declarations(declarations, indent + 1);
+        self.text.push_str(&format!("{}}}\n", pad));
+      }
+      Rule::At(AtRule {
+        span,
+        name,
+        prelude,
+        body,
+      }) => {
+        self.add_mapping(*span, indent);
+        self.text.push_str(&format!("{}@{}", pad, name));
+        if !prelude.is_empty() {
+          self.text.push(' ');
+          self.print_value(prelude);
+        }
+        match body {
+          AtRuleBody::None => self.text.push_str(";\n"),
+          AtRuleBody::Rules(rules) => {
+            self.text.push_str(" {\n");
+            for rule in rules {
+              self.print_rule(rule, indent + 1);
+            }
+            self.text.push_str(&format!("{}}}\n", pad));
+          }
+          AtRuleBody::Declarations(declarations) => {
+            self.text.push_str(" {\n");
+            self.print_declarations(declarations, indent + 1);
+            self.text.push_str(&format!("{}}}\n", pad));
+          }
+          AtRuleBody::Keyframes(keyframes) => {
+            self.text.push_str(" {\n");
+            for Keyframe {
+              selectors,
+              declarations,
+            } in keyframes
+            {
+              self
+                .text
+                .push_str(&format!("{}  {} {{\n", pad, selectors.join(", ")));
+              self.print_declarations(declarations, indent + 2);
+              self.text.push_str(&format!("{}  }}\n", pad));
+            }
+            self.text.push_str(&format!("{}}}\n", pad));
+          }
+        }
+      }
+    }
+  }
+
+  fn print_declarations(&mut self, declarations: &[Declaration], indent: usize) {
+    let pad = "  ".repeat(indent);
+    for Declaration { property, value } in declarations {
+      self.text.push_str(&format!("{}{}: ", pad, property));
+      self.print_value(value);
+      self.text.push_str(";\n");
+    }
+  }
+
+  fn print_value(&mut self, value: &[ValuePart]) {
+    for part in value {
+      match part {
+        ValuePart::Text(text) => self.text.push_str(text),
+        ValuePart::Expr(idx) => {
+          self.quasis.push(std::mem::take(&mut self.text));
+          self.exprs.push(*idx);
+        }
+      }
+    }
+  }
+}
+
+fn print_expr(expr: &Expr) -> String {
+  let source_map = Rc::new(SourceMap::default());
+  let mut buf = Vec::new();
+  {
+    let mut emitter = Emitter {
+      cfg: swc_ecmascript::codegen::Config { minify: false },
+      comments: None,
+      cm: source_map.clone(),
+      wr: Box::new(JsWriter::new(source_map, "\n", &mut buf, None)),
+    };
+    expr.emit_with(&mut emitter).unwrap();
+  }
+  String::from_utf8(buf).unwrap()
+}
+
+/// A parser of the CSS source, the template expressions are marked by the `PLACEHOLDER`.
+struct Parser {
+  chars: Vec<char>,
+  positions: Vec<BytePos>,
+  pos: usize,
+  /// the position of the rule or the declaration being parsed, the errors are located at it
+  start: usize,
+}
+
+/// Parse the rules of the CSS source, returns the error message with the span of the rule or
+/// the declaration that fails.
+fn parse_rules(source: &str, positions: Vec<BytePos>) -> Result<Vec<Rule>, (String, Span)> {
+  let mut parser = Parser {
+    chars: strip_comments(source).chars().collect(),
+    positions,
+    pos: 0,
+    start: 0,
+  };
+  parser
+    .parse_rules(false)
+    .map_err(|message| (message, parser.span_at(parser.start)))
+}
+
+impl Parser {
+  fn span_at(&self, pos: usize) -> Span {
+    match self.positions.get(pos) {
+      Some(lo) => Span::new(*lo, *lo, Default::default()),
+      None => DUMMY_SP,
+    }
+  }
+
+  fn peek(&self) -> Option<char> {
+    self.chars.get(self.pos).copied()
+  }
+
+  fn skip_whitespace(&mut self) {
+    while let Some(c) = self.peek() {
+      if !c.is_whitespace() {
+        break;
+      }
+      self.pos += 1;
+    }
+  }
+
+  /// read until one of the `stops` at the top level, strings and brackets are skipped
+  fn read_until(&mut self, stops: &[char]) -> String {
+    let mut text = String::new();
+    let mut depth = 0;
+    while let Some(c) = self.peek() {
+      if depth == 0 && stops.contains(&c) {
+        break;
+      }
+      match c {
+        '"' | '\'' => {
+          text.push(c);
+          self.pos += 1;
+          while let Some(s) = self.peek() {
+            text.push(s);
+            self.pos += 1;
+            if s == '\\' {
+              if let Some(escaped) = self.peek() {
+                text.push(escaped);
+                self.pos += 1;
+              }
+            } else if s == c {
+              break;
+            }
+          }
+          continue;
+        }
+        '(' | '[' => depth += 1,
+        ')' | ']' if depth > 0 => depth -= 1,
+        _ => {}
+      }
+      text.push(c);
+      self.pos += 1;
+    }
+    text
+  }
+
+  fn parse_rules(&mut self, nested: bool) -> Result<Vec<Rule>, String> {
+    let mut rules: Vec<Rule> = vec![];
+    loop {
+      self.skip_whitespace();
+      self.start = self.pos;
+      match self.peek() {
+        None if nested => return Err("unclosed block, expected `}`".into()),
+        None => return Ok(rules),
+        Some('}') if nested => {
+          self.pos += 1;
+          return Ok(rules);
+        }
+        Some('}') => return Err("unexpected `}`".into()),
+        Some(';') => self.pos += 1,
+        Some('@') => {
+          let span = self.span_at(self.pos);
+          self.pos += 1;
+          rules.push(Rule::At(self.parse_at_rule(span)?));
+        }
+        Some(_) => {
+          let span = self.span_at(self.pos);
+          let selectors = self.read_until(&['{', '}', ';']);
+          if self.peek() != Some('{') {
+            return Err(format!(
+              "expected `{{` after selector `{}`",
+              selectors.trim()
+            ));
+          }
+          self.pos += 1;
+          rules.push(Rule::Style(StyleRule {
+            span,
+            selectors: parse_selectors(&selectors)?,
+            declarations: self.parse_declarations()?,
+          }));
+        }
+      }
+    }
+  }
+
+  fn parse_at_rule(&mut self, span: Span) -> Result<AtRule, String> {
+    let mut name = String::new();
+    while let Some(c) = self.peek() {
+      if !(c.is_alphanumeric() || c == '-' || c == '_') {
+        break;
+      }
+      name.push(c);
+      self.pos += 1;
+    }
+    if name.is_empty() {
+      return Err("missing at-rule name after `@`".into());
+    }
+    let prelude = parse_value(self.read_until(&['{', ';', '}']).trim());
+    let body = match self.peek() {
+      Some('{') => {
+        self.pos += 1;
+        if name.ends_with("keyframes") {
+          AtRuleBody::Keyframes(self.parse_keyframes()?)
+        } else {
+          match name.as_str() {
+            "font-face" | "page" | "counter-style" | "property" | "viewport" => {
+              AtRuleBody::Declarations(self.parse_declarations()?)
+            }
+            _ => AtRuleBody::Rules(self.parse_rules(true)?),
+          }
+        }
+      }
+      Some(';') => {
+        self.pos += 1;
+        AtRuleBody::None
+      }
+      _ => AtRuleBody::None,
+    };
+    Ok(AtRule {
+      span,
+      name,
+      prelude,
+      body,
+    })
+  }
+
+  fn parse_keyframes(&mut self) -> Result<Vec<Keyframe>, String> {
+    let mut keyframes: Vec<Keyframe> = vec![];
+    loop {
+      self.skip_whitespace();
+      self.start = self.pos;
+      match self.peek() {
+        None => return Err("unclosed keyframes, expected `}`".into()),
+        Some('}') => {
+          self.pos += 1;
+          return Ok(keyframes);
+        }
+        Some(_) => {
+          let selectors = self.read_until(&['{', '}', ';']);
+          if self.peek() != Some('{') {
+            return Err(format!(
+              "expected `{{` after keyframe `{}`",
+              selectors.trim()
+            ));
+          }
+          self.pos += 1;
+          keyframes.push(Keyframe {
+            selectors: selectors.split(',').map(|s| s.trim().to_string()).collect(),
+            declarations: self.parse_declarations()?,
+          });
+        }
+      }
+    }
+  }
+
+  fn parse_declarations(&mut self) -> Result<Vec<Declaration>, String> {
+    let mut declarations: Vec<Declaration> = vec![];
+    loop {
+      self.skip_whitespace();
+      self.start = self.pos;
+      match self.peek() {
+        None => return Err("unclosed block, expected `}`".into()),
+        Some('}') => {
+          self.pos += 1;
+          return Ok(declarations);
+        }
+        Some(';') => self.pos += 1,
+        Some(_) => {
+          let property = self.read_until(&[':', ';', '{', '}']);
+          if self.peek() != Some(':') {
+            return Err(format!("expected `:` after property `{}`", property.trim()));
+          }
+          self.pos += 1;
+          let property = property.trim();
+          if property.contains(PLACEHOLDER) {
+            return Err("template expressions are not allowed in property names".into());
+          }
+          let value = self.read_until(&[';', '{', '}']);
+          if self.peek() == Some('{') {
+            return Err(format!("unexpected `{{` after `{}`", property));
+          }
+          declarations.push(Declaration {
+            property: property.into(),
+            value: parse_value(value.trim()),
+          });
+        }
+      }
+    }
+  }
+}
+
+/// split the text into the text parts and the template expressions
+fn parse_value(text: &str) -> Vec<ValuePart> {
+  let mut parts: Vec<ValuePart> = vec![];
+  for (i, part) in text.split(PLACEHOLDER).enumerate() {
+    if i % 2 == 1 {
+      parts.push(ValuePart::Expr(part.parse().unwrap()));
+    } else if !part.is_empty() {
+      parts.push(ValuePart::Text(part.into()));
+    }
+  }
+  parts
+}
+
+fn parse_selectors(text: &str) -> Result<Vec<Selector>, String> {
+  if text.contains(PLACEHOLDER) {
+    return Err("template expressions are not allowed in selectors".into());
+  }
+  let mut selectors: Vec<Selector> = vec![];
+  let mut depth = 0;
+  let mut start = 0;
+  for (i, c) in text.char_indices() {
+    match c {
+      '(' | '[' => depth += 1,
+      ')' | ']' => depth -= 1,
+      ',' if depth == 0 => {
+        selectors.push(parse_selector(&text[start..i])?);
+        start = i + 1;
+      }
+      _ => {}
+    }
+  }
+  selectors.push(parse_selector(&text[start..])?);
+  Ok(selectors)
+}
+
+fn parse_selector(text: &str) -> Result<Selector, String> {
+  let chars: Vec<char> = text.trim().chars().collect();
+  if chars.is_empty() {
+    return Err("empty selector".into());
+  }
+  let mut compounds: Vec<CompoundSelector> = vec![];
+  let mut combinator: Option<Combinator> = None;
+  let mut pos = 0;
+  while pos < chars.len() {
+    let c = chars[pos];
+    if c.is_whitespace() {
+      if !compounds.is_empty() && combinator.is_none() {
+        combinator = Some(Combinator::Descendant);
+      }
+      pos += 1;
+      continue;
+    }
+    let explicit = match c {
+      '>' => Some(Combinator::Child),
+      '+' => Some(Combinator::NextSibling),
+      '~' => Some(Combinator::SubsequentSibling),
+      _ => None,
+    };
+    if let Some(explicit) = explicit {
+      if compounds.is_empty() {
+        return Err(format!("unexpected `{}` in selector `{}`", c, text.trim()));
+      }
+      combinator = Some(explicit);
+      pos += 1;
+      continue;
+    }
+    let mut compound = parse_compound_selector(&chars, &mut pos, text)?;
+    compound.combinator = if compounds.is_empty() {
+      None
+    } else {
+      combinator.take()
+    };
+    compounds.push(compound);
+  }
+  if combinator.is_some() && combinator != Some(Combinator::Descendant) {
+    return Err(format!("unexpected end of selector `{}`", text.trim()));
+  }
+  Ok(Selector { compounds })
+}
+
+fn parse_compound_selector(
+  chars: &[char],
+  pos: &mut usize,
+  text: &str,
+) -> Result<CompoundSelector, String> {
+  let mut compound = CompoundSelector::default();
+  while *pos < chars.len() {
+    let c = chars[*pos];
+    match c {
+      '*' => {
+        compound.tag = Some("*".into());
+        *pos += 1;
+      }
+      '#' => {
+        *pos += 1;
+        compound.id = Some(read_name(chars, pos));
+      }
+      '.' => {
+        *pos += 1;
+        compound.classes.push(read_name(chars, pos));
+      }
+      '[' => {
+        *pos += 1;
+        let attr = read_group(chars, pos, ']');
+        compound.attrs.push(attr);
+      }
+      ':' => {
+        *pos += 1;
+        if chars.get(*pos) == Some(&':') {
+          *pos += 1;
+          compound.pseudo_element = Some(read_pseudo(chars, pos));
+        } else {
+          let pseudo = read_pseudo(chars, pos);
+          if pseudo.starts_with("global(") && pseudo.ends_with(')') {
+            compound.global = Some(pseudo[7..pseudo.len() - 1].trim().into());
+          } else {
+            compound.pseudo_classes.push(pseudo);
+          }
+        }
+      }
+      c if c.is_whitespace() || c == '>' || c == '+' || c == '~' => break,
+      c if is_name_char(c) => compound.tag = Some(read_name(chars, pos)),
+      c => return Err(format!("unexpected `{}` in selector `{}`", c, text.trim())),
+    }
+  }
+  Ok(compound)
+}
+
+fn is_name_char(c: char) -> bool {
+  c.is_alphanumeric() || c == '-' || c == '_' || c == '\\' || !c.is_ascii()
+}
+
+fn read_name(chars: &[char], pos: &mut usize) -> String {
+  let mut name = String::new();
+  while *pos < chars.len() && is_name_char(chars[*pos]) {
+    if chars[*pos] == '\\' && *pos + 1 < chars.len() {
+      name.push(chars[*pos]);
+      *pos += 1;
+    }
+    name.push(chars[*pos]);
+    *pos += 1;
+  }
+  name
+}
+
+/// read the pseudo name with the arguments, like `nth-child(2n + 1)`
+fn read_pseudo(chars: &[char], pos: &mut usize) -> String {
+  let mut pseudo = read_name(chars, pos);
+  if chars.get(*pos) == Some(&'(') {
+    *pos += 1;
+    pseudo.push('(');
+    pseudo.push_str(&read_group(chars, pos, ')'));
+    pseudo.push(')');
+  }
+  pseudo
+}
+
+/// read the text until the `close` char at the same level
+fn read_group(chars: &[char], pos: &mut usize, close: char) -> String {
+  let open = if close == ')' { '(' } else { '[' };
+  let mut depth = 0;
+  let mut text = String::new();
+  while *pos < chars.len() {
+    let c = chars[*pos];
+    *pos += 1;
+    if c == open {
+      depth += 1;
+    } else if c == close {
+      if depth == 0 {
+        break;
+      }
+      depth -= 1;
+    }
+    text.push(c);
+  }
+  text
+}
+
+/// replace the `/* ... */` comments in the source with spaces, strings are kept
+fn strip_comments(source: &str) -> String {
+  let mut output = String::with_capacity(source.len());
+  let mut chars = source.chars().peekable();
+  let mut quote: Option<char> = None;
+  while let Some(c) = chars.next() {
+    if let Some(q) = quote {
+      output.push(c);
+      if c == '\\' {
+        if let Some(escaped) = chars.next() {
+          output.push(escaped);
+        }
+      } else if c == q {
+        quote = None;
+      }
+      continue;
+    }
+    match c {
+      '"' | '\'' => {
+        quote = Some(c);
+        output.push(c);
+      }
+      '/' if chars.peek() == Some(&'*') => {
+        chars.next();
+        output.push_str("  ");
+        let mut prev = ' ';
+        for c in chars.by_ref() {
+          // keep the length and the lines of the source for the source positions
+          output.push(if c == '\n' { c } else { ' ' });
+          if prev == '*' && c == '/' {
+            break;
+          }
+          prev = c;
+        }
+      }
+      _ => output.push(c),
+    }
+  }
+  output
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_rules() {
+    let source = format!(
+      r#"
+      /* comment */
+      h1, .title > a:hover::after {{
+        font-size: 200%;
+        content: "a;b";
+      }}
+      p {{ color: {}0{} }}
+      @media (max-width: 600px) {{
+        ul li:nth-child(2n + 1) {{ display: none !important }}
+      }}
+      @keyframes fade {{
+        from {{ opacity: 0 }}
+        50%, to {{ opacity: 1 }}
+      }}
+      :global(body) main {{ margin: 0 }}
+      @import url("./a.css");
+    "#,
+      PLACEHOLDER, PLACEHOLDER
+    );
+    let rules = parse_rules(&source, vec![]).unwrap();
+    assert_eq!(rules.len(), 6);
+
+    if let Rule::Style(StyleRule {
+      selectors,
+      declarations,
+      ..
+    }) = &rules[0]
+    {
+      assert_eq!(selectors.len(), 2);
+      assert_eq!(selectors[0].to_string(), "h1");
+      assert_eq!(selectors[1].to_string(), ".title > a:hover::after");
+      let a = &selectors[1].compounds[1];
+      assert_eq!(a.combinator, Some(Combinator::Child));
+      assert_eq!(a.tag.as_deref(), Some("a"));
+      assert_eq!(a.pseudo_classes, vec!["hover".to_string()]);
+      assert_eq!(a.pseudo_element.as_deref(), Some("after"));
+      assert_eq!(declarations.len(), 2);
+      assert_eq!(
+        declarations[1].value,
+        vec![ValuePart::Text("\"a;b\"".into())]
+      );
+    } else {
+      panic!("expect a style rule")
+    }
+
+    if let Rule::Style(StyleRule { declarations, .. }) = &rules[1] {
+      assert_eq!(declarations[0].value, vec![ValuePart::Expr(0)]);
+    } else {
+      panic!("expect a style rule")
+    }
+
+    if let Rule::At(AtRule {
+      name,
+      prelude,
+      body: AtRuleBody::Rules(rules),
+      ..
+    }) = &rules[2]
+    {
+      assert_eq!(name, "media");
+      assert_eq!(prelude, &vec![ValuePart::Text("(max-width: 600px)".into())]);
+      assert_eq!(rules.len(), 1);
+    } else {
+      panic!("expect a media rule")
+    }
+
+    if let Rule::At(AtRule {
+      body: AtRuleBody::Keyframes(keyframes),
+      ..
+    }) = &rules[3]
+    {
+      assert_eq!(keyframes.len(), 2);
+      assert_eq!(keyframes[1].selectors, vec!["50%", "to"]);
+    } else {
+      panic!("expect a keyframes rule")
+    }
+
+    if let Rule::Style(StyleRule { selectors, .. }) = &rules[4] {
+      assert_eq!(selectors[0].compounds[0].global.as_deref(), Some("body"));
+      assert_eq!(selectors[0].to_string(), "body main");
+    } else {
+      panic!("expect a style rule")
+    }
+
+    assert!(matches!(
+      &rules[5],
+      Rule::At(AtRule {
+        body: AtRuleBody::None,
+        ..
+      })
+    ));
+  }
+
+  #[test]
+  fn test_scope() {
+    let mut css = CSS {
+      span: DUMMY_SP,
+      rules: parse_rules(
+        r#"
+        h1, p.title { color: red }
+        ul > li:hover { color: blue }
+        :global(.dark) a[href^="https"] { color: white }
+        @media print { em { display: none } }
+      "#,
+        vec![],
+      )
+      .unwrap(),
+      exprs: vec![],
+    };
+    let elements = vec![
+      ElementShape {
+        tag: "p".into(),
+        classes: vec!["title".into()],
+        attrs: vec!["class".into()],
+        ..ElementShape::default()
+      },
+      ElementShape {
+        tag: "li".into(),
+        ..ElementShape::default()
+      },
+      ElementShape {
+        tag: "a".into(),
+        is_dynamic: true,
+        ..ElementShape::default()
+      },
+    ];
+    let (compounds, unused) = css.scope("alef-x", &elements);
+    assert_eq!(compounds.len(), 2);
+    assert_eq!(
+      unused
+        .iter()
+        .map(|(selectors, _)| selectors.as_str())
+        .collect::<Vec<_>>(),
+      vec!["h1", "ul > li:hover", "em"]
+    );
+    assert!(compounds[0].matches(&elements[0]));
+    assert!(compounds[1].matches(&elements[2]));
+    let template = css.to_template();
+    assert_eq!(
+      template.quasis,
+      vec![[
+        "p.title.alef-x {",
+        "  color: red;",
+        "}",
+        "/* unused ul > li:hover */",
+        ".dark a.alef-x[href^=\"https\"] {",
+        "  color: white;",
+        "}",
+        "@media print {",
+        "  /* unused em */",
+        "}",
+        "",
+      ]
+      .join("\n")]
+    );
+    css.strip_unused();
+    assert_eq!(
+      css.to_template().quasis,
+      vec![[
+        "p.title.alef-x {",
+        "  color: red;",
+        "}",
+        ".dark a.alef-x[href^=\"https\"] {",
+        "  color: white;",
+        "}",
+        "",
+      ]
+      .join("\n")]
+    );
+  }
+
+  #[test]
+  fn test_bind_vars() {
+    let source = format!(
+      "@media (min-width: {p}0{p}) {{ p {{ color: {p}1{p}; margin: {p}2{p} }} }}",
+      p = PLACEHOLDER
+    );
+    let mut css = CSS {
+      span: DUMMY_SP,
+      rules: parse_rules(&source, vec![]).unwrap(),
+      exprs: vec![],
+    };
+    assert_eq!(css.bind_vars("alef-x", |idx| idx != 2), vec![1]);
+    let (quasis, exprs) = css.print();
+    assert_eq!(exprs, vec![0, 2]);
+    assert_eq!(
+      quasis,
+      vec![
+        "@media (min-width: ",
+        ") {\n  p {\n    color: var(--alef-x-1);\n    margin: ",
+        ";\n  }\n}\n"
+      ]
+    );
+  }
+
+  #[test]
+  fn test_parse_errors() {
+    assert!(parse_rules("p { color: red", vec![]).is_err());
+    assert!(parse_rules("p { color red }", vec![]).is_err());
+    assert!(parse_rules("p > { color: red }", vec![]).is_err());
+    assert!(parse_rules("}", vec![]).is_err());
+
+    // located at the declaration that fails
+    let source = "p { color: red }\nh1 { color blue }";
+    let positions = (0..source.len() as u32).map(BytePos).collect();
+    let (_, span) = parse_rules(source, positions).unwrap_err();
+    assert_eq!(span.lo, BytePos(22));
   }
 }
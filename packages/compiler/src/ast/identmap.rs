@@ -1,9 +1,11 @@
 // Copyright 2020-2021 postUI Lab. All rights reserved. MIT license.
 
 use indexmap::{IndexMap, IndexSet};
-use std::default::Default;
+use std::{default::Default, iter};
+use swc_common::DUMMY_SP;
 use swc_ecma_ast::*;
 use swc_ecma_utils::quote_ident;
+use swc_ecma_visit::{noop_visit_type, Node, Visit, VisitWith};
 
 pub type IdentSet = IndexSet<String>;
 
@@ -15,9 +17,12 @@ pub struct IdentMap {
     pub array_states: IdentSet,
     pub async_states: IdentSet,
     pub memos: IdentSet,
+    /// the (transitive) dependencies of the memos, keyed by the state index of the memo
+    pub memo_deps: IndexMap<usize, Vec<usize>>,
     pub props: IdentSet,
     pub slotss: IdentSet,
     pub contexts: IdentSet,
+    pub components: IdentSet,
 }
 
 impl IdentMap {
@@ -33,6 +38,7 @@ impl IdentMap {
                 "prop" => self.props.insert(id),
                 "slots" => self.slotss.insert(id),
                 "context" => self.contexts.insert(id),
+                "component" => self.components.insert(id),
                 _ => false,
             };
         }
@@ -68,10 +74,51 @@ impl IdentMap {
         self.mark_as_state(pat);
         self.mark_as("context", pat);
     }
+    pub fn mark_component(&mut self, pat: &Pat) {
+        self.mark(pat);
+        self.mark_as("component", pat);
+    }
+    /// check whether the JSX element is a component imported from a `.alef` module or declared
+    /// by `FC`, shared by the DOM and the SSR outputs to render the same tree
+    pub fn is_component(&self, name: &JSXElementName) -> bool {
+        match name {
+            JSXElementName::Ident(Ident { sym, .. }) => self.components.contains(sym.as_ref()),
+            _ => false,
+        }
+    }
+    /// mark the params of a list row as states, the row states take the indices
+    /// after the component states, returns the count of the component states
+    pub fn enter_row(&mut self, params: &[Pat]) -> usize {
+        let len = self.states.len();
+        for pat in params {
+            for ident in get_idents_from_pat(pat) {
+                self.states.insert(ident.sym.as_ref().to_string());
+            }
+        }
+        len
+    }
+    pub fn leave_row(&mut self, len: usize) {
+        self.states.truncate(len);
+    }
+    /// share the helpers and the scope idents of the parent component to avoid name clashes
+    pub fn inherit(&mut self, parent: &IdentMap) {
+        self.helpers = parent.helpers.clone();
+        self.scopes.extend(parent.scopes.iter().cloned());
+    }
+    /// merge the helpers created by an inline component back
+    pub fn merge_helpers(&mut self, child: &IdentMap) {
+        for (name, rename) in &child.helpers {
+            self.scopes.insert(rename.clone());
+            self.helpers.insert(name.clone(), rename.clone());
+        }
+    }
     pub fn create_ident(&mut self, name: &str) -> Ident {
         let is_helper = match name {
-            "Component" | "Element" | "Fragment" | "If" | "List" | "Text" | "Style" | "Memo"
-            | "Effect" | "Dirty" | "nope" => true,
+            "Component" | "New" | "Element" | "Fragment" | "If" | "IfElse" | "List" | "Text"
+            | "Slot" | "Style" | "Memo" | "Effect" | "Dirty" | "Transition" | "Refresh" | "Source" | "nope" => true,
+            // helpers of the SSR runtime
+            "escape" | "escapeStyle" | "attr" | "spreadAttrs" | "renderSlot" | "suspend"
+            | "resolveAsync" | "asyncPlaceholder" => true,
             _ => false,
         };
         if is_helper && self.helpers.contains_key(name.into()) {
@@ -104,12 +151,31 @@ impl IdentMap {
         }
     }
 
+    /// collect all the states that the expression depends on
+    pub fn collect_deps(&self, expr: &Expr, deps: &mut Vec<usize>) {
+        let mut collector = DepsCollector {
+            ident_map: self,
+            deps,
+        };
+        expr.visit_with(&Invalid { span: DUMMY_SP } as _, &mut collector);
+    }
+
+    /// add the state to the dependencies, a memo brings its own dependencies as well
+    fn push_dep(&self, name: &str, deps: &mut Vec<usize>) {
+        if let Some(dep) = self.states.get_index_of(name) {
+            let memo_deps = self.memo_deps.get(&dep).into_iter().flatten();
+            for dep in iter::once(&dep).chain(memo_deps) {
+                if !deps.contains(dep) {
+                    deps.push(*dep);
+                }
+            }
+        }
+    }
+
     pub fn convert_memo_expr(&self, expr: Expr, deps: &mut Vec<usize>) -> Expr {
         match expr {
             Expr::Ident(id) => {
-                if let Some(dep) = self.states.get_index_of(id.sym.as_ref().into()) {
-                    deps.push(dep);
-                }
+                self.push_dep(id.sym.as_ref(), deps);
                 Expr::Ident(id)
             }
             Expr::Update(UpdateExpr {
@@ -220,7 +286,10 @@ impl IdentMap {
                 type_params,
                 return_type,
             }),
-            _ => expr,
+            _ => {
+                self.collect_deps(&expr, deps);
+                expr
+            }
         }
     }
 
@@ -364,14 +433,47 @@ impl Default for IdentMap {
             array_states: IdentSet::new(),
             async_states: IdentSet::new(),
             memos: IdentSet::new(),
+            memo_deps: IndexMap::new(),
             props: IdentSet::new(),
             slotss: IdentSet::new(),
             contexts: IdentSet::new(),
+            components: IdentSet::new(),
+        }
+    }
+}
+
+/// A visitor to collect the states used by an expression.
+struct DepsCollector<'a> {
+    ident_map: &'a IdentMap,
+    deps: &'a mut Vec<usize>,
+}
+
+impl<'a> Visit for DepsCollector<'a> {
+    noop_visit_type!();
+
+    fn visit_ident(&mut self, id: &Ident, _: &dyn Node) {
+        self.ident_map.push_dep(id.sym.as_ref(), self.deps);
+    }
+
+    fn visit_member_expr(&mut self, expr: &MemberExpr, _: &dyn Node) {
+        expr.obj.visit_with(expr as _, self);
+        if expr.computed {
+            expr.prop.visit_with(expr as _, self);
         }
     }
+
+    fn visit_prop_name(&mut self, name: &PropName, _: &dyn Node) {
+        if let PropName::Computed(computed) = name {
+            computed.visit_with(name as _, self);
+        }
+    }
+
+    fn visit_jsx_element_name(&mut self, _: &JSXElementName, _: &dyn Node) {}
+
+    fn visit_jsx_attr_name(&mut self, _: &JSXAttrName, _: &dyn Node) {}
 }
 
-fn get_idents_from_pat(pat: &Pat) -> Vec<Ident> {
+pub fn get_idents_from_pat(pat: &Pat) -> Vec<Ident> {
     let mut idents: Vec<Ident> = vec![];
     match pat {
         Pat::Ident(id) => {
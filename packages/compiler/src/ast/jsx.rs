@@ -1,17 +1,56 @@
 // Copyright 2020-2021 postUI Lab. All rights reserved. MIT license.
 
-use super::identmap::IdentMap;
+use super::{
+    css::{CompoundSelector, ElementShape, CSS},
+    identmap::IdentMap,
+    ssr::{COMPONENT_MARKER, IF_MARKER, LIST_MARKER, SLOT_MARKER},
+    statement::{AsyncStatus, TemplateStatement},
+    validator::{DUPLICATE_TRANSITION, MISSING_KEY, UNSUPPORTED_SPREAD_CHILD},
+};
 use crate::resolve::Resolver;
+use indexmap::IndexMap;
 use regex::Regex;
 use std::{cell::RefCell, iter, mem, rc::Rc};
-use swc_common::{iter::IdentifyLast, DUMMY_SP};
+use swc_common::{errors::DiagnosticId, iter::IdentifyLast, SourceMap, Span, DUMMY_SP};
 use swc_ecma_ast::*;
-use swc_ecma_utils::{member_expr, quote_ident, ExprFactory, HANDLER};
+use swc_ecma_utils::{member_expr, quote_ident, quote_str, ExprFactory, HANDLER};
+use swc_ecma_visit::{noop_visit_type, Node, Visit, VisitWith};
 
+/// The scope of the component styles.
 #[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StyleScope {
+    /// the class name appended to the selectors and the matched elements
+    pub class_name: String,
+    /// the compound selectors of the used rules
+    pub compounds: Vec<CompoundSelector>,
+}
+
+impl StyleScope {
+    /// add the scoped class name to the element if any of the selectors can match it
+    pub fn apply(&self, opening: &mut JSXOpeningElement) {
+        if let Some(shape) = element_shape(opening) {
+            if self
+                .compounds
+                .iter()
+                .any(|compound| compound.matches(&shape))
+            {
+                add_class_name(&mut opening.attrs, &self.class_name);
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct JSXTransformer {
     pub resolver: Rc<RefCell<Resolver>>,
     pub scope_idents: Rc<RefCell<IdentMap>>,
+    pub style_scope: Option<StyleScope>,
+    pub source_map: Rc<SourceMap>,
+    /// development mode, the elements are located in the source and the props are asserted
+    pub is_dev: bool,
+    /// the cursor over the server-rendered DOM in hydrate mode, the nodes are adopted from it
+    /// instead of being created
+    pub cursor: Option<Ident>,
 }
 
 impl JSXTransformer {
@@ -20,10 +59,32 @@ impl JSXTransformer {
         scope_idents.create_ident(name)
     }
 
-    pub fn transform_element(&self, el: JSXElement) -> Expr {
+    /// transform `<p />` to `Element("p", null)` and `<Hello name={name} />` to
+    /// `New(Hello, { name: Memo(() => name, [0]) })`
+    pub fn transform_element(&self, mut el: JSXElement) -> Expr {
+        if self.scope_idents.borrow().is_component(&el.opening.name) {
+            return self.transform_component(el);
+        }
+        if let JSXElementName::Ident(Ident { sym, .. }) = &el.opening.name {
+            if sym.eq("slot") {
+                return self.transform_slot_element(el);
+            }
+        }
+        if let Some(style_scope) = &self.style_scope {
+            style_scope.apply(&mut el.opening);
+        }
+        let span = el.span;
+        let transitions = take_transitions(&mut el);
+        if let Some(cursor) = &self.cursor {
+            let element = self.claim_element(cursor, el, span);
+            if transitions.is_empty() {
+                return element;
+            }
+            return self.transform_transitions(element, transitions);
+        }
         let element_ident = self.create_ident("Element");
-        Expr::Call(CallExpr {
-            span: DUMMY_SP,
+        let element = Expr::Call(CallExpr {
+            span: if self.is_dev { span } else { DUMMY_SP },
             callee: ExprOrSuper::Expr(Box::new(Expr::Ident(element_ident))),
             args: iter::once(jsx_name(el.opening.name).as_arg())
                 .chain(iter::once(self.transform_attrs(el.opening.attrs).as_arg()))
@@ -34,6 +95,321 @@ impl JSXTransformer {
                 })
                 .collect(),
             type_args: Default::default(),
+        });
+        let element = if self.is_dev {
+            self.locate(element, span)
+        } else {
+            element
+        };
+        if transitions.is_empty() {
+            return element;
+        }
+        self.transform_transitions(element, transitions)
+    }
+
+    /// wrap the element with its source location in development mode:
+    /// `Source(Element("p", null), "./App.alef:3:9")`
+    fn locate(&self, element: Expr, span: Span) -> Expr {
+        let location = self.location(span);
+        let source_ident = self.create_ident("Source");
+        Expr::Call(CallExpr {
+            span: DUMMY_SP,
+            callee: ExprOrSuper::Expr(Box::new(Expr::Ident(source_ident))),
+            args: vec![element.as_arg(), Lit::Str(quote_str!(location)).as_arg()],
+            type_args: None,
+        })
+    }
+
+    /// the source location of the span, like `./App.alef:3:9`
+    fn location(&self, span: Span) -> String {
+        let loc = self.source_map.lookup_char_pos(span.lo);
+        format!(
+            "{}:{}:{}",
+            self.resolver.borrow().specifier,
+            loc.line,
+            loc.col.0 + 1
+        )
+    }
+
+    /// adopt the element from the server-rendered DOM in hydrate mode:
+    /// `cursor.element("p", { onClick: Dirty(...) }, (cursor) => [cursor.text("Count: "),
+    /// cursor.text(Memo(...))])`, and the claim is located by `cursor.at("./App.alef:3:9")`
+    /// in development mode to report the hydration mismatches
+    fn claim_element(&self, cursor: &Ident, el: JSXElement, span: Span) -> Expr {
+        let children: Vec<ExprOrSpread> = el
+            .children
+            .into_iter()
+            .filter_map(|c| self.transform_child(c))
+            .collect();
+        let mut args = vec![
+            jsx_name(el.opening.name).as_arg(),
+            self.transform_attrs(el.opening.attrs).as_arg(),
+        ];
+        if !children.is_empty() {
+            let children = Expr::Array(ArrayLit {
+                span: DUMMY_SP,
+                elems: children.into_iter().map(Some).collect(),
+            });
+            args.push(
+                self.create_init(BlockStmtOrExpr::Expr(Box::new(children)))
+                    .as_arg(),
+            );
+        }
+        let cursor = if self.is_dev {
+            Expr::Call(CallExpr {
+                span: DUMMY_SP,
+                callee: create_member_expr(Expr::Ident(cursor.clone()), "at").as_callee(),
+                args: vec![Lit::Str(quote_str!(self.location(span))).as_arg()],
+                type_args: None,
+            })
+        } else {
+            Expr::Ident(cursor.clone())
+        };
+        Expr::Call(CallExpr {
+            span: if self.is_dev { span } else { DUMMY_SP },
+            callee: create_member_expr(cursor, "element").as_callee(),
+            args,
+            type_args: None,
+        })
+    }
+
+    /// adopt the nodes between the hydration markers in hydrate mode:
+    /// `cursor.block("if", If(...))` for `<!--if-->...<!--/if-->`
+    fn claim_block(&self, marker: &str, node: Expr) -> Expr {
+        match &self.cursor {
+            Some(cursor) => self.claim(
+                cursor,
+                "block",
+                vec![Lit::Str(quote_str!(marker)).as_arg(), node.as_arg()],
+            ),
+            None => node,
+        }
+    }
+
+    /// create `cursor.method(...args)`
+    fn claim(&self, cursor: &Ident, method: &str, args: Vec<ExprOrSpread>) -> Expr {
+        Expr::Call(CallExpr {
+            span: DUMMY_SP,
+            callee: create_member_expr(Expr::Ident(cursor.clone()), method).as_callee(),
+            args,
+            type_args: None,
+        })
+    }
+
+    /// create the function that creates the nodes of a block, it takes the cursor of the block
+    /// in hydrate mode: `(cursor) => cursor.element("p", null)`, the nodes are adopted when the
+    /// block is hydrated and created when the block is rendered again
+    fn create_init(&self, body: BlockStmtOrExpr) -> Expr {
+        Expr::Arrow(ArrowExpr {
+            span: DUMMY_SP,
+            params: self.cursor.iter().cloned().map(Pat::Ident).collect(),
+            body,
+            is_async: false,
+            is_generator: false,
+            type_params: None,
+            return_type: None,
+        })
+    }
+
+    /// transform `<p in:fly={{ y }} out:fade>` to
+    /// `Transition(Element("p", null), { in: [fly, Memo(() => ({ y }), [0])], out: [fade] })`,
+    /// the node is removed after the `out` transition completes
+    fn transform_transitions(&self, element: Expr, transitions: Vec<TransitionDirective>) -> Expr {
+        let transition_ident = self.create_ident("Transition");
+        let props = transitions
+            .into_iter()
+            .map(
+                |TransitionDirective {
+                     directive,
+                     name,
+                     params,
+                 }| {
+                    let elems = iter::once(Expr::Ident(name))
+                        .chain(params.map(|params| self.transform_expr(params, false)))
+                        .map(|expr| Some(expr.as_arg()))
+                        .collect();
+                    PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
+                        key: PropName::Ident(directive),
+                        value: Box::new(Expr::Array(ArrayLit {
+                            span: DUMMY_SP,
+                            elems,
+                        })),
+                    })))
+                },
+            )
+            .collect();
+        Expr::Call(CallExpr {
+            span: DUMMY_SP,
+            callee: ExprOrSuper::Expr(Box::new(Expr::Ident(transition_ident))),
+            args: vec![
+                element.as_arg(),
+                Expr::Object(ObjectLit {
+                    span: DUMMY_SP,
+                    props,
+                })
+                .as_arg(),
+            ],
+            type_args: None,
+        })
+    }
+
+    /// transform `<Logo><img slot="icon" /><span>Alef</span></Logo>` to
+    /// `New(Logo, null, { icon: () => Element("img", null), default: () => Element("span", null, "Alef") })`
+    fn transform_component(&self, el: JSXElement) -> Expr {
+        let mut slots: IndexMap<String, Vec<Expr>> = IndexMap::new();
+        for child in el.children {
+            let (name, node) = match child {
+                JSXElementChild::JSXElement(mut el) => match take_attr(&mut el, "slot") {
+                    Some(Expr::Lit(Lit::Str(Str { value, .. }))) => {
+                        (value.as_ref().to_string(), self.transform_element(*el))
+                    }
+                    _ => ("default".into(), self.transform_element(*el)),
+                },
+                child => match self.transform_child(child) {
+                    Some(ExprOrSpread { expr, .. }) => ("default".into(), *expr),
+                    None => continue,
+                },
+            };
+            slots.entry(name).or_default().push(node);
+        }
+
+        let new_ident = self.create_ident("New");
+        let mut args = vec![
+            jsx_name(el.opening.name).as_arg(),
+            self.transform_attrs(el.opening.attrs).as_arg(),
+        ];
+        if !slots.is_empty() {
+            args.push(
+                Expr::Object(ObjectLit {
+                    span: DUMMY_SP,
+                    props: slots
+                        .into_iter()
+                        .map(|(name, nodes)| {
+                            PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
+                                key: PropName::Ident(quote_ident!(name)),
+                                value: Box::new(self.create_init(BlockStmtOrExpr::Expr(Box::new(
+                                    self.create_node(nodes),
+                                )))),
+                            })))
+                        })
+                        .collect(),
+                })
+                .as_arg(),
+            );
+        }
+        let component = Expr::Call(CallExpr {
+            span: DUMMY_SP,
+            callee: ExprOrSuper::Expr(Box::new(Expr::Ident(new_ident))),
+            args,
+            type_args: Default::default(),
+        });
+        self.claim_block(COMPONENT_MARKER, component)
+    }
+
+    /// transform `<slot name="icon"><img /></slot>` to
+    /// `Slot((props.$$slots || {}).icon, () => Element("img", null))`
+    fn transform_slot_element(&self, mut el: JSXElement) -> Expr {
+        let name = match take_attr(&mut el, "name") {
+            Some(Expr::Lit(Lit::Str(Str { value, .. }))) => value.as_ref().to_string(),
+            _ => "default".into(),
+        };
+        let slot = Expr::Member(MemberExpr {
+            span: DUMMY_SP,
+            obj: ExprOrSuper::Expr(Box::new(Expr::Paren(ParenExpr {
+                span: DUMMY_SP,
+                expr: Box::new(create_slots_expr()),
+            }))),
+            prop: Box::new(Expr::Ident(quote_ident!(name))),
+            computed: false,
+        });
+        let fallback: Vec<Expr> = el
+            .children
+            .into_iter()
+            .filter_map(|c| self.transform_child(c))
+            .map(|ExprOrSpread { expr, .. }| *expr)
+            .collect();
+        self.create_slot(
+            slot,
+            if fallback.is_empty() {
+                None
+            } else {
+                Some(self.create_node(fallback))
+            },
+        )
+    }
+
+    /// transform `{slots}`, `{slots.icon}` and `{slots.icon || <img />}` to
+    /// `Slot(slots.icon, () => Element("img", null))`, returns the expression back if it is not a slot
+    fn transform_slot_expr(&self, expr: Expr) -> Result<Expr, Box<Expr>> {
+        match expr {
+            Expr::Bin(BinExpr {
+                op: BinaryOp::LogicalOr,
+                left,
+                right,
+                ..
+            })
+            | Expr::Bin(BinExpr {
+                op: BinaryOp::NullishCoalescing,
+                left,
+                right,
+                ..
+            }) if self.is_slot(left.as_ref()) => {
+                let fallback = self.transform_node(*right);
+                Ok(self.create_slot(*left, Some(fallback)))
+            }
+            expr if self.is_slot(&expr) => Ok(self.create_slot(expr, None)),
+            expr => Err(Box::new(expr)),
+        }
+    }
+
+    /// check whether the expression is a slot declared by `Prop<Slots>`
+    fn is_slot(&self, expr: &Expr) -> bool {
+        let slotss = &self.scope_idents.borrow().slotss;
+        match expr {
+            Expr::Ident(Ident { sym, .. }) => slotss.contains(sym.as_ref()),
+            Expr::Member(MemberExpr {
+                obj: ExprOrSuper::Expr(obj),
+                computed: false,
+                ..
+            }) => match obj.as_ref() {
+                Expr::Ident(Ident { sym, .. }) => slotss.contains(sym.as_ref()),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// create `Slot(slot, fallback)`, the slot is a slot function or the slots object that
+    /// renders its default slot
+    fn create_slot(&self, slot: Expr, fallback: Option<Expr>) -> Expr {
+        let slot_ident = self.create_ident("Slot");
+        let mut args = vec![slot.as_arg()];
+        if let Some(fallback) = fallback {
+            args.push(
+                self.create_init(BlockStmtOrExpr::Expr(Box::new(fallback)))
+                    .as_arg(),
+            );
+        }
+        let slot = Expr::Call(CallExpr {
+            span: DUMMY_SP,
+            callee: ExprOrSuper::Expr(Box::new(Expr::Ident(slot_ident))),
+            args,
+            type_args: Default::default(),
+        });
+        self.claim_block(SLOT_MARKER, slot)
+    }
+
+    /// create a node of the nodes, multiple nodes are wrapped by `Fragment`
+    fn create_node(&self, mut nodes: Vec<Expr>) -> Expr {
+        if nodes.len() == 1 {
+            return nodes.pop().unwrap();
+        }
+        let frag_ident = self.create_ident("Fragment");
+        Expr::Call(CallExpr {
+            span: DUMMY_SP,
+            callee: ExprOrSuper::Expr(Box::new(Expr::Ident(frag_ident))),
+            args: nodes.into_iter().map(|node| node.as_arg()).collect(),
+            type_args: Default::default(),
         })
     }
 
@@ -51,8 +427,509 @@ impl JSXTransformer {
         })
     }
 
-    pub fn transform_condition(&self, if_stmt: IfStmt) -> Stmt {
-        Stmt::Empty(EmptyStmt { span: DUMMY_SP })
+    /// transform `if (a) <p /> else if (b) <p /> else <p />` to
+    /// `IfElse([[() => a, () => Element("p")], ...], [deps])`, and a single branch like
+    /// `a && <p />` to `If(() => a, () => Element("p"), [deps])`
+    pub fn transform_condition(&self, if_stmt: IfStmt) -> Expr {
+        let steps = condition_steps(if_stmt);
+        let mut deps: Vec<usize> = vec![];
+        let mut branches: Vec<(Expr, Expr)> = vec![];
+        for (test, cons) in steps {
+            let test = match test {
+                Some(test) => {
+                    self.scope_idents.borrow().collect_deps(&test, &mut deps);
+                    test
+                }
+                None => Expr::Lit(Lit::Bool(Bool {
+                    span: DUMMY_SP,
+                    value: true,
+                })),
+            };
+            branches.push((expr_to_arrow(test), self.transform_branch(cons)));
+        }
+
+        if branches.len() == 1 {
+            let (test, init) = branches.pop().unwrap();
+            let if_ident = self.create_ident("If");
+            let if_node = Expr::Call(CallExpr {
+                span: DUMMY_SP,
+                callee: ExprOrSuper::Expr(Box::new(Expr::Ident(if_ident))),
+                args: vec![
+                    test.as_arg(),
+                    init.as_arg(),
+                    create_deps_array(deps).as_arg(),
+                ],
+                type_args: Default::default(),
+            });
+            return self.claim_block(IF_MARKER, if_node);
+        }
+
+        let if_else_ident = self.create_ident("IfElse");
+        let if_else_node = Expr::Call(CallExpr {
+            span: DUMMY_SP,
+            callee: ExprOrSuper::Expr(Box::new(Expr::Ident(if_else_ident))),
+            args: vec![
+                Expr::Array(ArrayLit {
+                    span: DUMMY_SP,
+                    elems: branches
+                        .into_iter()
+                        .map(|(test, init)| {
+                            Some(
+                                Expr::Array(ArrayLit {
+                                    span: DUMMY_SP,
+                                    elems: vec![Some(test.as_arg()), Some(init.as_arg())],
+                                })
+                                .as_arg(),
+                            )
+                        })
+                        .collect(),
+                })
+                .as_arg(),
+                create_deps_array(deps).as_arg(),
+            ],
+            type_args: Default::default(),
+        });
+        self.claim_block(IF_MARKER, if_else_node)
+    }
+
+    /// transform a branch of the condition to a function that creates the node
+    fn transform_branch(&self, stmt: Stmt) -> Expr {
+        let is_block = matches!(stmt, Stmt::Block(_));
+        let stmts = match stmt {
+            Stmt::Block(BlockStmt { stmts, .. }) => stmts,
+            stmt => vec![stmt],
+        };
+        let mut body: Vec<Stmt> = vec![];
+        let mut nodes: Vec<Expr> = vec![];
+        for stmt in stmts {
+            match stmt {
+                Stmt::Expr(ExprStmt { expr, .. }) if is_block && !is_jsx(expr.as_ref()) => body
+                    .push(Stmt::Expr(ExprStmt {
+                        span: DUMMY_SP,
+                        expr,
+                    })),
+                Stmt::Expr(ExprStmt { expr, .. }) => nodes.push(self.transform_node(*expr)),
+                stmt => body.push(stmt),
+            }
+        }
+        let node = self.create_node(nodes);
+        if body.is_empty() {
+            return self.create_init(BlockStmtOrExpr::Expr(Box::new(node)));
+        }
+        body.push(Stmt::Return(ReturnStmt {
+            span: DUMMY_SP,
+            arg: Some(Box::new(node)),
+        }));
+        self.create_init(BlockStmtOrExpr::BlockStmt(BlockStmt {
+            span: DUMMY_SP,
+            stmts: body,
+        }))
+    }
+
+    /// transform `const double: Memo<number> = n * 2` to `Memo(() => double = n * 2, [deps])`,
+    /// the dependencies are recorded so the memos and nodes depending on it invalidate transitively
+    pub fn transform_memo(&self, ident: &Ident, init: Expr) -> Expr {
+        let mut deps: Vec<usize> = vec![];
+        {
+            let mut scope_idents = self.scope_idents.borrow_mut();
+            scope_idents.collect_deps(&init, &mut deps);
+            if let Some(idx) = scope_idents.states.get_index_of(ident.sym.as_ref()) {
+                deps.retain(|dep| *dep != idx);
+                scope_idents.memo_deps.insert(idx, deps.clone());
+            }
+        }
+        let memo_ident = self.create_ident("Memo");
+        Expr::Call(CallExpr {
+            span: DUMMY_SP,
+            callee: ExprOrSuper::Expr(Box::new(Expr::Ident(memo_ident))),
+            args: vec![
+                expr_to_arrow(Expr::Assign(AssignExpr {
+                    span: DUMMY_SP,
+                    op: AssignOp::Assign,
+                    left: PatOrExpr::Pat(Box::new(Pat::Ident(quote_ident!(ident.sym.clone())))),
+                    right: Box::new(init),
+                }))
+                .as_arg(),
+                create_deps_array(deps).as_arg(),
+            ],
+            type_args: Default::default(),
+        })
+    }
+
+    /// transform `const title: Prop<string> = "Hello"` to `let { title = "Hello" } = props`,
+    /// and listen the prop changes by `this.listen("title", Dirty(() => ..., [deps]))`
+    pub fn transform_prop(&self, name: Pat, init: Option<Expr>) -> Vec<Stmt> {
+        let types = if self.is_dev {
+            prop_types(&name, init.is_some())
+        } else {
+            vec![]
+        };
+        let props = match prop_to_pat_props(name, init) {
+            Some(props) => props,
+            None => return vec![],
+        };
+        let mut stmts = vec![create_let_stmt(
+            create_object_pat(props.clone()),
+            Some(Expr::Ident(quote_ident!("props"))),
+        )];
+        for prop in props {
+            let key = match &prop {
+                ObjectPatProp::Assign(AssignPatProp { key, .. }) => key.sym.as_ref().to_string(),
+                ObjectPatProp::KeyValue(KeyValuePatProp { key, .. }) => match key {
+                    PropName::Ident(Ident { sym, .. }) => sym.as_ref().to_string(),
+                    PropName::Str(Str { value, .. }) => value.as_ref().to_string(),
+                    _ => continue,
+                },
+                ObjectPatProp::Rest(_) => continue,
+            };
+            // the runtime calls the listeners without arguments after `this.props[key] = value`
+            let update = Expr::Arrow(ArrowExpr {
+                span: DUMMY_SP,
+                params: vec![],
+                body: BlockStmtOrExpr::BlockStmt(BlockStmt {
+                    span: DUMMY_SP,
+                    stmts: vec![expr_to_stmt(Expr::Assign(AssignExpr {
+                        span: DUMMY_SP,
+                        op: AssignOp::Assign,
+                        left: PatOrExpr::Pat(Box::new(create_object_pat(vec![prop]))),
+                        right: member_expr!(DUMMY_SP, this.props),
+                    }))],
+                }),
+                is_async: false,
+                is_generator: false,
+                type_params: None,
+                return_type: None,
+            });
+            stmts.push(Stmt::Expr(ExprStmt {
+                span: DUMMY_SP,
+                expr: Box::new(Expr::Call(CallExpr {
+                    span: DUMMY_SP,
+                    callee: member_expr!(DUMMY_SP, this.listen).as_callee(),
+                    args: vec![
+                        Lit::Str(quote_str!(key)).as_arg(),
+                        self.transform_expr(update, true).as_arg(),
+                    ],
+                    type_args: None,
+                })),
+            }));
+        }
+        // this.assertProps({ title: "string", count: "number|undefined" })
+        if !types.is_empty() {
+            let types = Expr::Object(ObjectLit {
+                span: DUMMY_SP,
+                props: types
+                    .into_iter()
+                    .map(|(key, types)| {
+                        PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
+                            key: PropName::Ident(quote_ident!(key)),
+                            value: Box::new(Expr::Lit(Lit::Str(quote_str!(types)))),
+                        })))
+                    })
+                    .collect(),
+            });
+            stmts.push(create_this_call("assertProps", vec![types.as_arg()]));
+        }
+        stmts
+    }
+
+    /// transform `const theme: Context<"theme"> = "light"` to provide the context to the descendants:
+    /// `this.provide("theme", Memo(() => theme, [deps]))`, and a context without initializer
+    /// consumes the nearest provider: `this.consume("theme", Dirty((value) => { theme = value }, [deps]))`
+    pub fn transform_context(&self, name: Pat, ctx_name: String, init: Option<Expr>) -> Vec<Stmt> {
+        let mut stmts: Vec<Stmt> = vec![];
+        let (method, arg) = match (name, init) {
+            (Pat::Ident(ident), Some(init)) => {
+                let memo = self.transform_memo(&ident, init);
+                let ident = quote_ident!(ident.sym);
+                stmts.push(create_let_stmt(Pat::Ident(ident.clone()), None));
+                stmts.push(expr_to_stmt(memo));
+                ("provide", self.transform_expr(Expr::Ident(ident), false))
+            }
+            (name, _) => {
+                let name = strip_pat_type(name);
+                stmts.push(create_let_stmt(name.clone(), None));
+                (
+                    "consume",
+                    self.transform_expr(create_assign_arrow(name), true),
+                )
+            }
+        };
+        stmts.push(create_this_call(
+            method,
+            vec![Lit::Str(quote_str!(ctx_name)).as_arg(), arg.as_arg()],
+        ));
+        stmts
+    }
+
+    /// transform `let data = await fetch(url)` to `let data` and
+    /// `this.await(fetch(url), Dirty((value) => { data = value }, [deps]))`, the component renders
+    /// the `$t_pending` fallback until the promise settles, and the `$t_rejected` fallback if
+    /// the promise is rejected
+    pub fn transform_async_state(&self, name: Pat, promise: Expr) -> Vec<Stmt> {
+        let name = strip_pat_type(name);
+        let update = self.transform_expr(create_assign_arrow(name.clone()), true);
+        vec![
+            create_let_stmt(name, None),
+            create_this_call("await", vec![promise.as_arg(), update.as_arg()]),
+        ]
+    }
+
+    /// transform `$catch: (error, reset) => <p>{error.message}</p>` to
+    /// `this.catch((error, reset) => Element("p", null, error.message))`, the component catches the
+    /// errors thrown by the child components, the memos and the effects of its subtree, and renders
+    /// the fallback instead, calling `reset` renders the subtree again
+    pub fn transform_catch(&self, params: Vec<Pat>, template: TemplateStatement) -> Stmt {
+        let node = self.transform_client_template(template);
+        let fallback = Expr::Arrow(ArrowExpr {
+            span: DUMMY_SP,
+            params: params.into_iter().map(strip_pat_type).collect(),
+            body: BlockStmtOrExpr::Expr(Box::new(node)),
+            is_async: false,
+            is_generator: false,
+            type_params: None,
+            return_type: None,
+        });
+        create_this_call("catch", vec![fallback.as_arg()])
+    }
+
+    /// transform `$t_pending: <p>Loading...</p>` to
+    /// `this.fallback("pending", Element("p", null, "Loading..."))`
+    pub fn transform_fallback(&self, status: AsyncStatus, template: TemplateStatement) -> Stmt {
+        let node = self.transform_client_template(template);
+        let status = match status {
+            AsyncStatus::Pending => "pending",
+            AsyncStatus::Rejected => "rejected",
+        };
+        create_this_call(
+            "fallback",
+            vec![Lit::Str(quote_str!(status)).as_arg(), node.as_arg()],
+        )
+    }
+
+    /// transform the template rendered on the client only, like the fallbacks, its nodes are
+    /// created even in hydrate mode
+    fn transform_client_template(&self, template: TemplateStatement) -> Expr {
+        let transformer = JSXTransformer {
+            cursor: None,
+            ..self.clone()
+        };
+        match template {
+            TemplateStatement::Element(el) => transformer.transform_element(el),
+            TemplateStatement::Fragment(frag) => transformer.transform_fragment(frag),
+            TemplateStatement::If(if_stmt) => transformer.transform_condition(if_stmt),
+        }
+    }
+
+    /// transform `const slots: Prop<Slots>` to `let slots = props.$$slots || {}`
+    pub fn transform_slots(&self, name: Pat) -> Stmt {
+        create_let_stmt(strip_pat_type(name), Some(create_slots_expr()))
+    }
+
+    /// transform `$: console.log(n)` and `$_log: () => { ...; return cleanup }` to
+    /// `Effect(() => { ... }, [deps], "log")`, the cleanup function returned by the effect
+    /// will be invoked before the effect re-runs and when the component is unmounted
+    pub fn transform_side_effect(&self, name: Option<String>, stmt: Stmt) -> Expr {
+        let effect = match stmt {
+            Stmt::Expr(ExprStmt { expr, .. }) => match unwrap_paren(*expr) {
+                expr @ Expr::Arrow(_) | expr @ Expr::Fn(_) => expr,
+                expr => block_to_arrow(vec![expr_to_stmt(expr)]),
+            },
+            Stmt::Block(BlockStmt { stmts, .. }) => block_to_arrow(stmts),
+            stmt => block_to_arrow(vec![stmt]),
+        };
+        let mut deps: Vec<usize> = vec![];
+        self.scope_idents.borrow().collect_deps(&effect, &mut deps);
+        let effect_ident = self.create_ident("Effect");
+        let mut args = vec![effect.as_arg(), create_deps_array(deps).as_arg()];
+        if let Some(name) = name {
+            args.push(Lit::Str(quote_str!(name)).as_arg());
+        }
+        Expr::Call(CallExpr {
+            span: DUMMY_SP,
+            callee: ExprOrSuper::Expr(Box::new(Expr::Ident(effect_ident))),
+            args,
+            type_args: Default::default(),
+        })
+    }
+
+    /// transform `$style: \`p { color: ${color} }\`` to
+    /// `Style("alef-xxxxxxxx", () => \`p.alef-xxxxxxxx { color: var(--alef-xxxxxxxx-0); }\`, { "--alef-xxxxxxxx-0": Memo(() => color, [0]) })`,
+    /// the `Style` helper patches the custom properties when the memos are updated instead of
+    /// re-generating the whole sheet. Expressions in the at-rule preludes can't be custom properties,
+    /// the template is wrapped by a `Memo` if any of them depends on the states.
+    pub fn transform_style(&self, css: &mut CSS) -> Expr {
+        let style_ident = self.create_ident("Style");
+        let class_name = match &self.style_scope {
+            Some(StyleScope { class_name, .. }) => class_name.clone(),
+            None => String::new(),
+        };
+        let mut exprs_deps: Vec<Vec<usize>> = vec![];
+        css.exprs = mem::take(&mut css.exprs)
+            .into_iter()
+            .map(|expr| {
+                let mut deps: Vec<usize> = vec![];
+                let expr = self.convert_memo_expr(expr, &mut deps);
+                exprs_deps.push(deps);
+                expr
+            })
+            .collect();
+        let bound = css.bind_vars(&class_name, |idx| !exprs_deps[idx].is_empty());
+        let mut template_deps: Vec<usize> = vec![];
+        for (idx, deps) in exprs_deps.iter().enumerate() {
+            if !bound.contains(&idx) {
+                template_deps.extend(deps);
+            }
+        }
+        let template = expr_to_arrow(Expr::Tpl(css.to_tpl()));
+        let mut args = vec![
+            Expr::Lit(Lit::Str(quote_str!(class_name.as_str()))).as_arg(),
+            if template_deps.is_empty() {
+                template.as_arg()
+            } else {
+                self.create_memo(template, template_deps).as_arg()
+            },
+        ];
+        if !bound.is_empty() {
+            args.push(
+                ObjectLit {
+                    span: DUMMY_SP,
+                    props: bound
+                        .into_iter()
+                        .map(|idx| {
+                            PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
+                                key: PropName::Str(quote_str!(format!("--{}-{}", class_name, idx))),
+                                value: Box::new(self.create_memo(
+                                    expr_to_arrow(css.exprs[idx].clone()),
+                                    exprs_deps[idx].clone(),
+                                )),
+                            })))
+                        })
+                        .collect(),
+                }
+                .as_arg(),
+            );
+        }
+        let style = Expr::Call(CallExpr {
+            span: DUMMY_SP,
+            callee: ExprOrSuper::Expr(Box::new(Expr::Ident(style_ident))),
+            args,
+            type_args: None,
+        });
+        // adopt the `<style>` element rendered by the server in hydrate mode
+        match &self.cursor {
+            Some(cursor) => self.claim(cursor, "style", vec![style.as_arg()]),
+            None => style,
+        }
+    }
+
+    fn create_memo(&self, arrow: Expr, deps: Vec<usize>) -> Expr {
+        let memo_ident = self.create_ident("Memo");
+        Expr::Call(CallExpr {
+            span: DUMMY_SP,
+            callee: ExprOrSuper::Expr(Box::new(Expr::Ident(memo_ident))),
+            args: vec![arrow.as_arg(), create_deps_array(deps).as_arg()],
+            type_args: None,
+        })
+    }
+
+    /// transform `items.map(item => <li key={item.id} />)` to
+    /// `List(() => items, (item) => ({ key: item.id, create: () => Element("li") }), [deps])`,
+    /// the params of the row are marked as row states, so a row only updates when its own item changes
+    fn transform_list(&self, list: ListExpr) -> Expr {
+        let ListExpr {
+            items,
+            params,
+            mut stmts,
+            node,
+        } = list;
+        let mut deps: Vec<usize> = vec![];
+        self.scope_idents.borrow().collect_deps(&items, &mut deps);
+
+        let len = self.scope_idents.borrow_mut().enter_row(&params);
+        let (key, node) = take_key(node);
+        if let (true, None, Expr::JSXElement(el)) = (self.is_dev, &key, &node) {
+            HANDLER.with(|handler| {
+                handler
+                    .struct_span_warn(el.span, "missing `key` attribute of the list row")
+                    .code(DiagnosticId::Lint(MISSING_KEY.into()))
+                    .help("add a unique `key` to keep the DOM nodes of the rows when the list is reordered")
+                    .emit()
+            });
+        }
+        let node = self.transform_node(node);
+        self.scope_idents.borrow_mut().leave_row(len);
+
+        let mut props: Vec<PropOrSpread> = vec![];
+        if let Some(key) = key {
+            props.push(PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
+                key: PropName::Ident(quote_ident!("key")),
+                value: Box::new(key),
+            }))));
+        }
+        props.push(PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
+            key: PropName::Ident(quote_ident!("create")),
+            value: Box::new(self.create_init(BlockStmtOrExpr::Expr(Box::new(node)))),
+        }))));
+        let row = Expr::Object(ObjectLit {
+            span: DUMMY_SP,
+            props,
+        });
+        let body = if stmts.is_empty() {
+            BlockStmtOrExpr::Expr(Box::new(Expr::Paren(ParenExpr {
+                span: DUMMY_SP,
+                expr: Box::new(row),
+            })))
+        } else {
+            stmts.push(Stmt::Return(ReturnStmt {
+                span: DUMMY_SP,
+                arg: Some(Box::new(row)),
+            }));
+            BlockStmtOrExpr::BlockStmt(BlockStmt {
+                span: DUMMY_SP,
+                stmts,
+            })
+        };
+
+        let list_ident = self.create_ident("List");
+        let list = Expr::Call(CallExpr {
+            span: DUMMY_SP,
+            callee: ExprOrSuper::Expr(Box::new(Expr::Ident(list_ident))),
+            args: vec![
+                expr_to_arrow(items).as_arg(),
+                Expr::Arrow(ArrowExpr {
+                    span: DUMMY_SP,
+                    params,
+                    body,
+                    is_async: false,
+                    is_generator: false,
+                    type_params: None,
+                    return_type: None,
+                })
+                .as_arg(),
+                create_deps_array(deps).as_arg(),
+            ],
+            type_args: Default::default(),
+        });
+        self.claim_block(LIST_MARKER, list)
+    }
+
+    /// transform a JSX node or an expression to a node, the text of the expression is adopted
+    /// from the node after the `<!--t-->` marker in hydrate mode: `cursor.text(Memo(...))`
+    fn transform_node(&self, expr: Expr) -> Expr {
+        match unwrap_paren(expr) {
+            Expr::JSXElement(el) => self.transform_element(*el),
+            Expr::JSXFragment(frag) => self.transform_fragment(frag),
+            expr => match expr_to_list(expr) {
+                Ok(list) => self.transform_list(list),
+                Err(expr) => {
+                    let text = self.transform_expr(*expr, false);
+                    match &self.cursor {
+                        Some(cursor) => self.claim(cursor, "text", vec![text.as_arg()]),
+                        None => text,
+                    }
+                }
+            },
+        }
     }
 
     fn transform_child(&self, c: JSXElementChild) -> Option<ExprOrSpread> {
@@ -67,16 +944,35 @@ impl JSXTransformer {
                 if s.value.is_empty() {
                     return None;
                 }
-                Lit::Str(s).as_arg()
+                // the static texts are adopted as well in hydrate mode, they are kept for the
+                // blocks that are created on the client
+                match &self.cursor {
+                    Some(cursor) => self.claim(cursor, "text", vec![Lit::Str(s).as_arg()]),
+                    None => Expr::Lit(Lit::Str(s)),
+                }
+                .as_arg()
             }
             JSXElementChild::JSXElement(el) => self.transform_element(*el).as_arg(),
             JSXElementChild::JSXFragment(el) => self.transform_fragment(el).as_arg(),
             JSXElementChild::JSXExprContainer(JSXExprContainer { expr, .. }) => match expr {
-                JSXExpr::Expr(e) => self.transform_expr(*e, false).as_arg(),
+                JSXExpr::Expr(e) => match self.transform_slot_expr(unwrap_paren(*e)) {
+                    Ok(slot) => slot.as_arg(),
+                    Err(e) => match expr_to_if_stmt(*e) {
+                        Ok(if_stmt) => self.transform_condition(if_stmt).as_arg(),
+                        Err(e) => self.transform_node(*e).as_arg(),
+                    },
+                },
                 JSXExpr::JSXEmptyExpr(..) => return None,
             },
-            JSXElementChild::JSXSpreadChild(JSXSpreadChild { .. }) => {
-                unimplemented!("jsx sperad child")
+            JSXElementChild::JSXSpreadChild(JSXSpreadChild { span, .. }) => {
+                HANDLER.with(|handler| {
+                    handler
+                        .struct_span_err(span, "JSX spread children are not supported")
+                        .code(DiagnosticId::Error(UNSUPPORTED_SPREAD_CHILD.into()))
+                        .help("render the items with `.map()` instead")
+                        .emit()
+                });
+                return None;
             }
         })
     }
@@ -166,24 +1062,7 @@ impl JSXTransformer {
                 } else {
                     expr_to_arrow(expr).as_arg()
                 })
-                .chain(iter::once(
-                    Expr::Array(ArrayLit {
-                        span: DUMMY_SP,
-                        elems: deps
-                            .into_iter()
-                            .map(|dep| {
-                                Some(ExprOrSpread {
-                                    spread: None,
-                                    expr: Box::new(Expr::Lit(Lit::Num(Number {
-                                        span: DUMMY_SP,
-                                        value: dep as f64,
-                                    }))),
-                                })
-                            })
-                            .collect(),
-                    })
-                    .as_arg(),
-                ))
+                .chain(iter::once(create_deps_array(deps).as_arg()))
                 .collect(),
                 type_args: Default::default(),
             });
@@ -206,7 +1085,7 @@ impl JSXTransformer {
     }
 }
 
-fn jsx_name(name: JSXElementName) -> Box<Expr> {
+pub fn jsx_name(name: JSXElementName) -> Box<Expr> {
     match name {
         JSXElementName::Ident(i) => {
             if i.sym.eq("this") {
@@ -265,7 +1144,113 @@ fn convert_jsx_obj(obj: JSXObject) -> ExprOrSuper {
     }
 }
 
-fn attr_to_prop(a: JSXAttr) -> Prop {
+/// get the static shape of an intrinsic element, returns `None` for the components and slots
+pub fn element_shape(el: &JSXOpeningElement) -> Option<ElementShape> {
+    let tag = match &el.name {
+        JSXElementName::Ident(Ident { sym, .. })
+            if sym.starts_with(|c: char| c.is_ascii_lowercase()) && !sym.eq("slot") =>
+        {
+            sym.as_ref().to_string()
+        }
+        _ => return None,
+    };
+    let mut shape = ElementShape {
+        tag,
+        ..ElementShape::default()
+    };
+    for attr in &el.attrs {
+        match attr {
+            JSXAttrOrSpread::JSXAttr(JSXAttr {
+                name: JSXAttrName::Ident(Ident { sym, .. }),
+                value,
+                ..
+            }) => {
+                let name = match sym.as_ref() {
+                    "className" => "class",
+                    name => name,
+                };
+                let value = match value {
+                    Some(JSXAttrValue::Lit(Lit::Str(Str { value, .. }))) => Some(value.as_ref()),
+                    _ => None,
+                };
+                match (name, value) {
+                    ("class", Some(value)) => {
+                        shape.classes = value.split_whitespace().map(|c| c.into()).collect()
+                    }
+                    ("id", Some(value)) => shape.id = Some(value.into()),
+                    ("class", None) | ("id", None) => shape.is_dynamic = true,
+                    _ => {}
+                }
+                shape.attrs.push(name.into());
+            }
+            JSXAttrOrSpread::JSXAttr(..) => {}
+            JSXAttrOrSpread::SpreadElement(..) => shape.is_dynamic = true,
+        }
+    }
+    Some(shape)
+}
+
+/// A visitor to collect the shapes of the elements in the templates.
+#[derive(Default)]
+pub struct ElementShapeCollector {
+    pub shapes: Vec<ElementShape>,
+}
+
+impl Visit for ElementShapeCollector {
+    noop_visit_type!();
+
+    fn visit_jsx_opening_element(&mut self, el: &JSXOpeningElement, _: &dyn Node) {
+        if let Some(shape) = element_shape(el) {
+            self.shapes.push(shape);
+        }
+        el.visit_children_with(self);
+    }
+}
+
+/// append the class name to the `className` attribute, `class` is treated as an alias
+fn add_class_name(attrs: &mut Vec<JSXAttrOrSpread>, class_name: &str) {
+    for attr in attrs.iter_mut() {
+        if let JSXAttrOrSpread::JSXAttr(JSXAttr {
+            name: JSXAttrName::Ident(Ident { sym, .. }),
+            value,
+            ..
+        }) = attr
+        {
+            if sym.as_ref() != "className" && sym.as_ref() != "class" {
+                continue;
+            }
+            *value = Some(match value.take() {
+                Some(JSXAttrValue::Lit(Lit::Str(Str { value, .. }))) => {
+                    JSXAttrValue::Lit(Lit::Str(quote_str!(format!("{} {}", value, class_name))))
+                }
+                Some(JSXAttrValue::JSXExprContainer(JSXExprContainer {
+                    span,
+                    expr: JSXExpr::Expr(expr),
+                })) => JSXAttrValue::JSXExprContainer(JSXExprContainer {
+                    span,
+                    expr: JSXExpr::Expr(Box::new(Expr::Bin(BinExpr {
+                        span: DUMMY_SP,
+                        op: BinaryOp::Add,
+                        left: expr,
+                        right: Box::new(Expr::Lit(Lit::Str(quote_str!(format!(
+                            " {}",
+                            class_name
+                        ))))),
+                    }))),
+                }),
+                _ => JSXAttrValue::Lit(Lit::Str(quote_str!(class_name))),
+            });
+            return;
+        }
+    }
+    attrs.push(JSXAttrOrSpread::JSXAttr(JSXAttr {
+        span: DUMMY_SP,
+        name: JSXAttrName::Ident(quote_ident!("className")),
+        value: Some(JSXAttrValue::Lit(Lit::Str(quote_str!(class_name)))),
+    }));
+}
+
+pub fn attr_to_prop(a: JSXAttr) -> Prop {
     let key = to_prop_name(a.name);
     let value = a
         .value
@@ -321,7 +1306,7 @@ lazy_static! {
     static ref SPACE_NL_END: Regex = Regex::new("\\s*\n\\s*$").unwrap();
 }
 
-fn jsx_text_to_string(t: &str) -> String {
+pub fn jsx_text_to_string(t: &str) -> String {
     if t.eq(" ") {
         return t.into();
     }
@@ -354,7 +1339,572 @@ fn jsx_text_to_string(t: &str) -> String {
     buf.into()
 }
 
-fn expr_to_arrow(expr: Expr) -> Expr {
+/// A `.map()` call over an array which renders JSX.
+pub struct ListExpr {
+    /// the array to map, like `todos` or `todos.filter(todo => !todo.completed)`
+    pub items: Expr,
+    /// the params of the map callback
+    pub params: Vec<Pat>,
+    /// the statements of the callback before returning the node
+    pub stmts: Vec<Stmt>,
+    /// the JSX node returned by the callback
+    pub node: Expr,
+}
+
+/// convert `items.map(item => <li />)` and `items.map(function(item) { return <li /> })` to
+/// list, returns the expression back if it is not a list
+pub fn expr_to_list(expr: Expr) -> Result<ListExpr, Box<Expr>> {
+    if !is_list(&expr) {
+        return Err(Box::new(expr));
+    }
+    match expr {
+        Expr::Call(CallExpr {
+            callee: ExprOrSuper::Expr(callee),
+            mut args,
+            ..
+        }) => {
+            let items = match *callee {
+                Expr::Member(MemberExpr {
+                    obj: ExprOrSuper::Expr(obj),
+                    ..
+                }) => *obj,
+                _ => unreachable!(),
+            };
+            let (params, mut stmts) = match *args.remove(0).expr {
+                Expr::Arrow(ArrowExpr { params, body, .. }) => match body {
+                    BlockStmtOrExpr::Expr(expr) => (params, vec![expr_to_stmt(*expr)]),
+                    BlockStmtOrExpr::BlockStmt(BlockStmt { stmts, .. }) => (params, stmts),
+                },
+                Expr::Fn(FnExpr {
+                    function: Function { params, body, .. },
+                    ..
+                }) => (
+                    params.into_iter().map(|param| param.pat).collect(),
+                    body.map(|body| body.stmts).unwrap_or_default(),
+                ),
+                _ => unreachable!(),
+            };
+            let node = match stmts.pop() {
+                Some(Stmt::Return(ReturnStmt { arg: Some(arg), .. })) => *arg,
+                Some(Stmt::Expr(ExprStmt { expr, .. })) => *expr,
+                _ => unreachable!(),
+            };
+            Ok(ListExpr {
+                items,
+                params,
+                stmts,
+                node: unwrap_paren(node),
+            })
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// check whether the expression is a `.map()` call which renders JSX
+fn is_list(expr: &Expr) -> bool {
+    match expr {
+        Expr::Call(CallExpr {
+            callee: ExprOrSuper::Expr(callee),
+            args,
+            ..
+        }) if args.len() == 1 && args[0].spread.is_none() => {
+            let is_map = match callee.as_ref() {
+                Expr::Member(MemberExpr {
+                    obj: ExprOrSuper::Expr(_),
+                    prop,
+                    computed: false,
+                    ..
+                }) => matches!(prop.as_ref(), Expr::Ident(Ident { sym, .. }) if sym.eq("map")),
+                _ => false,
+            };
+            is_map
+                && match args[0].expr.as_ref() {
+                    Expr::Arrow(ArrowExpr {
+                        body: BlockStmtOrExpr::Expr(expr),
+                        ..
+                    }) => is_jsx(expr.as_ref()),
+                    Expr::Arrow(ArrowExpr {
+                        body: BlockStmtOrExpr::BlockStmt(BlockStmt { stmts, .. }),
+                        ..
+                    }) => returns_jsx(stmts),
+                    Expr::Fn(FnExpr {
+                        function:
+                            Function {
+                                body: Some(BlockStmt { stmts, .. }),
+                                is_generator: false,
+                                ..
+                            },
+                        ..
+                    }) => returns_jsx(stmts),
+                    _ => false,
+                }
+        }
+        _ => false,
+    }
+}
+
+/// check whether the statements end with returning JSX
+fn returns_jsx(stmts: &[Stmt]) -> bool {
+    match stmts.last() {
+        Some(Stmt::Return(ReturnStmt { arg: Some(arg), .. })) => is_jsx(arg.as_ref()),
+        _ => false,
+    }
+}
+
+/// take the `key` attribute out of the JSX element
+pub fn take_key(node: Expr) -> (Option<Expr>, Expr) {
+    match node {
+        Expr::JSXElement(mut el) => (take_attr(&mut el, "key"), Expr::JSXElement(el)),
+        _ => (None, node),
+    }
+}
+
+/// take the attribute out of the JSX element, returns the value of the attribute
+pub fn take_attr(el: &mut JSXElement, name: &str) -> Option<Expr> {
+    let idx = el.opening.attrs.iter().position(|attr| match attr {
+        JSXAttrOrSpread::JSXAttr(JSXAttr {
+            name: JSXAttrName::Ident(Ident { sym, .. }),
+            ..
+        }) => sym.eq(name),
+        _ => false,
+    });
+    match idx.map(|idx| el.opening.attrs.remove(idx)) {
+        Some(JSXAttrOrSpread::JSXAttr(attr)) => match attr_to_prop(attr) {
+            Prop::KeyValue(KeyValueProp { value, .. }) => Some(*value),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// A transition directive of the element, like `transition:fade={{ duration: 200 }}`.
+pub struct TransitionDirective {
+    /// `transition`, `in` or `out`
+    pub directive: Ident,
+    /// the transition function, like `fade`
+    pub name: Ident,
+    pub params: Option<Expr>,
+}
+
+/// check whether the attribute namespace is a transition directive
+pub fn is_transition_directive(ns: &str) -> bool {
+    matches!(ns, "transition" | "in" | "out")
+}
+
+/// take the transition directives out of the JSX element
+pub fn take_transitions(el: &mut JSXElement) -> Vec<TransitionDirective> {
+    let mut transitions: Vec<TransitionDirective> = vec![];
+    let mut attrs = vec![];
+    for attr in mem::take(&mut el.opening.attrs) {
+        let (ns, name, value) = match attr {
+            JSXAttrOrSpread::JSXAttr(JSXAttr {
+                name: JSXAttrName::JSXNamespacedName(JSXNamespacedName { ns, name }),
+                value,
+                ..
+            }) if is_transition_directive(ns.sym.as_ref()) => (ns, name, value),
+            attr => {
+                attrs.push(attr);
+                continue;
+            }
+        };
+        // `transition:` applies both of `in:` and `out:`
+        let conflicted = transitions.iter().any(|t| {
+            t.directive.sym.eq(&ns.sym)
+                || t.directive.sym.eq("transition")
+                || ns.sym.eq("transition")
+        });
+        if conflicted {
+            HANDLER.with(|handler| {
+                handler
+                    .struct_span_err(
+                        ns.span,
+                        &format!("duplicate transition directive `{}:{}`", ns.sym, name.sym),
+                    )
+                    .code(DiagnosticId::Error(DUPLICATE_TRANSITION.into()))
+                    .help("use one `in:` and one `out:` directive, or a single `transition:`")
+                    .emit()
+            });
+            continue;
+        }
+        let params = value.map(|value| {
+            match attr_to_prop(JSXAttr {
+                span: DUMMY_SP,
+                name: JSXAttrName::Ident(name.clone()),
+                value: Some(value),
+            }) {
+                Prop::KeyValue(KeyValueProp { value, .. }) => unwrap_paren(*value),
+                _ => unreachable!(),
+            }
+        });
+        transitions.push(TransitionDirective {
+            directive: ns,
+            name,
+            params,
+        });
+    }
+    el.opening.attrs = attrs;
+    transitions
+}
+
+/// create an array literal of the state dependencies, like `[0, 1]`
+fn create_deps_array(mut deps: Vec<usize>) -> Expr {
+    deps.sort_unstable();
+    deps.dedup();
+    Expr::Array(ArrayLit {
+        span: DUMMY_SP,
+        elems: deps
+            .into_iter()
+            .map(|dep| {
+                Some(ExprOrSpread {
+                    spread: None,
+                    expr: Box::new(Expr::Lit(Lit::Num(Number {
+                        span: DUMMY_SP,
+                        value: dep as f64,
+                    }))),
+                })
+            })
+            .collect(),
+    })
+}
+
+/// flatten the if statement to the steps of `(test, branch)`, the last step of `else` has no test
+pub fn condition_steps(if_stmt: IfStmt) -> Vec<(Option<Expr>, Stmt)> {
+    let mut steps: Vec<(Option<Expr>, Stmt)> = vec![];
+    let mut next = Some(Stmt::If(if_stmt));
+    while let Some(stmt) = next.take() {
+        match stmt {
+            Stmt::If(IfStmt {
+                test, cons, alt, ..
+            }) => {
+                steps.push((Some(*test), *cons));
+                next = alt.map(|alt| *alt);
+            }
+            Stmt::Expr(ExprStmt { expr, .. }) => match unwrap_paren(*expr) {
+                // match `a ? <p /> : b ? <p /> : <p />`
+                Expr::Cond(CondExpr {
+                    test, cons, alt, ..
+                }) => {
+                    steps.push((Some(*test), expr_to_stmt(*cons)));
+                    next = Some(expr_to_stmt(*alt));
+                }
+                // match `a ? <p /> : null`
+                Expr::Lit(Lit::Null(_)) => {}
+                Expr::Ident(Ident { ref sym, .. }) if sym.eq("undefined") => {}
+                expr => steps.push((None, expr_to_stmt(expr))),
+            },
+            stmt => steps.push((None, stmt)),
+        }
+    }
+    steps
+}
+
+/// convert `a && <p />`, `a || <p />` and `a ? <p /> : <p />` to if statement,
+/// returns the expression back if it does not render JSX
+pub fn expr_to_if_stmt(expr: Expr) -> Result<IfStmt, Box<Expr>> {
+    match expr {
+        Expr::Bin(BinExpr {
+            op: BinaryOp::LogicalAnd,
+            left,
+            right,
+            ..
+        }) if is_jsx(right.as_ref()) => Ok(IfStmt {
+            span: DUMMY_SP,
+            test: left,
+            cons: Box::new(expr_to_stmt(*right)),
+            alt: None,
+        }),
+        Expr::Bin(BinExpr {
+            op: BinaryOp::LogicalOr,
+            left,
+            right,
+            ..
+        }) if is_jsx(right.as_ref()) => Ok(IfStmt {
+            span: DUMMY_SP,
+            test: Box::new(Expr::Unary(UnaryExpr {
+                span: DUMMY_SP,
+                op: UnaryOp::Bang,
+                arg: left,
+            })),
+            cons: Box::new(expr_to_stmt(*right)),
+            alt: None,
+        }),
+        Expr::Cond(CondExpr {
+            test, cons, alt, ..
+        }) if is_jsx(cons.as_ref()) || is_jsx(alt.as_ref()) => Ok(IfStmt {
+            span: DUMMY_SP,
+            test,
+            cons: Box::new(expr_to_stmt(*cons)),
+            alt: Some(Box::new(expr_to_stmt(*alt))),
+        }),
+        _ => Err(Box::new(expr)),
+    }
+}
+
+/// check whether the expression renders JSX
+pub fn is_jsx(expr: &Expr) -> bool {
+    match expr {
+        Expr::JSXElement(_) | Expr::JSXFragment(_) => true,
+        Expr::Paren(ParenExpr { expr, .. }) => is_jsx(expr.as_ref()),
+        Expr::Cond(CondExpr { cons, alt, .. }) => is_jsx(cons.as_ref()) || is_jsx(alt.as_ref()),
+        _ => false,
+    }
+}
+
+pub fn unwrap_paren(expr: Expr) -> Expr {
+    match expr {
+        Expr::Paren(ParenExpr { expr, .. }) => unwrap_paren(*expr),
+        _ => expr,
+    }
+}
+
+pub fn expr_to_stmt(expr: Expr) -> Stmt {
+    Stmt::Expr(ExprStmt {
+        span: DUMMY_SP,
+        expr: Box::new(expr),
+    })
+}
+
+/// get the props of the object pattern to destruct a `Prop<T>` from the props,
+/// returns `None` if the name is not an identifier or an object pattern
+pub fn prop_to_pat_props(name: Pat, init: Option<Expr>) -> Option<Vec<ObjectPatProp>> {
+    match name {
+        Pat::Ident(Ident { sym, .. }) => Some(vec![ObjectPatProp::Assign(AssignPatProp {
+            span: DUMMY_SP,
+            key: quote_ident!(sym),
+            value: init.map(Box::new),
+        })]),
+        Pat::Object(ObjectPat { props, .. }) => Some(props),
+        _ => None,
+    }
+}
+
+/// derive the runtime types of the props from the `Prop<T>` annotation, like
+/// `[("title", "string"), ("count", "number|undefined")]`, the props of `any`, `unknown` or the
+/// types that can't be checked at runtime are not asserted
+pub fn prop_types(name: &Pat, has_init: bool) -> Vec<(String, String)> {
+    let type_param = |type_ann: &Option<TsTypeAnn>| match type_ann.as_ref()?.type_ann.as_ref() {
+        TsType::TsTypeRef(TsTypeRef {
+            type_params: Some(type_params),
+            ..
+        }) => type_params
+            .params
+            .first()
+            .map(|param| param.as_ref().clone()),
+        _ => None,
+    };
+    match name {
+        Pat::Ident(Ident { sym, type_ann, .. }) => type_param(type_ann)
+            .and_then(|ty| runtime_types(&ty))
+            .map(|types| vec![(sym.as_ref().to_owned(), join_types(types, has_init))])
+            .unwrap_or_default(),
+        Pat::Object(ObjectPat {
+            props, type_ann, ..
+        }) => {
+            let members = match type_param(type_ann) {
+                Some(TsType::TsTypeLit(TsTypeLit { members, .. })) => members,
+                _ => return vec![],
+            };
+            props
+                .iter()
+                .filter_map(|prop| {
+                    let (name, has_default) = match prop {
+                        ObjectPatProp::Assign(AssignPatProp { key, value, .. }) => {
+                            (key.sym.as_ref(), value.is_some())
+                        }
+                        ObjectPatProp::KeyValue(KeyValuePatProp {
+                            key: PropName::Ident(Ident { sym, .. }),
+                            value,
+                        }) => (sym.as_ref(), matches!(value.as_ref(), Pat::Assign(_))),
+                        _ => return None,
+                    };
+                    let (optional, ty) = members.iter().find_map(|member| match member {
+                        TsTypeElement::TsPropertySignature(TsPropertySignature {
+                            key,
+                            optional,
+                            computed: false,
+                            type_ann: Some(TsTypeAnn { type_ann, .. }),
+                            ..
+                        }) => match key.as_ref() {
+                            Expr::Ident(Ident { sym, .. }) if sym.as_ref() == name => {
+                                Some((*optional, type_ann.as_ref()))
+                            }
+                            _ => None,
+                        },
+                        _ => None,
+                    })?;
+                    let types = runtime_types(ty)?;
+                    Some((name.to_owned(), join_types(types, optional || has_default)))
+                })
+                .collect()
+        }
+        _ => vec![],
+    }
+}
+
+/// map the type to the runtime types, like `string | null` to `["string", "null"]`
+fn runtime_types(ty: &TsType) -> Option<Vec<&'static str>> {
+    Some(match ty {
+        TsType::TsKeywordType(TsKeywordType { kind, .. }) => vec![match kind {
+            TsKeywordTypeKind::TsStringKeyword => "string",
+            TsKeywordTypeKind::TsNumberKeyword => "number",
+            TsKeywordTypeKind::TsBooleanKeyword => "boolean",
+            TsKeywordTypeKind::TsBigIntKeyword => "bigint",
+            TsKeywordTypeKind::TsSymbolKeyword => "symbol",
+            TsKeywordTypeKind::TsObjectKeyword => "object",
+            TsKeywordTypeKind::TsUndefinedKeyword | TsKeywordTypeKind::TsVoidKeyword => "undefined",
+            TsKeywordTypeKind::TsNullKeyword => "null",
+            _ => return None,
+        }],
+        TsType::TsLitType(TsLitType { lit, .. }) => vec![match lit {
+            TsLit::Number(_) => "number",
+            TsLit::Str(_) | TsLit::Tpl(_) => "string",
+            TsLit::Bool(_) => "boolean",
+            TsLit::BigInt(_) => "bigint",
+        }],
+        TsType::TsFnOrConstructorType(_) => vec!["function"],
+        TsType::TsArrayType(_) | TsType::TsTupleType(_) => vec!["array"],
+        TsType::TsTypeRef(TsTypeRef {
+            type_name: TsEntityName::Ident(Ident { sym, .. }),
+            ..
+        }) if sym.eq("Array") => vec!["array"],
+        TsType::TsTypeLit(_) => vec!["object"],
+        TsType::TsParenthesizedType(TsParenthesizedType { type_ann, .. }) => {
+            return runtime_types(type_ann)
+        }
+        TsType::TsUnionOrIntersectionType(TsUnionOrIntersectionType::TsUnionType(
+            TsUnionType { types, .. },
+        )) => {
+            let mut union = vec![];
+            for ty in types {
+                union.extend(runtime_types(ty)?);
+            }
+            union
+        }
+        _ => return None,
+    })
+}
+
+fn join_types(mut types: Vec<&str>, optional: bool) -> String {
+    if optional {
+        types.push("undefined");
+    }
+    types.sort_unstable();
+    types.dedup();
+    types.join("|")
+}
+
+/// strip the type annotation of the identifier or the object pattern
+pub fn strip_pat_type(name: Pat) -> Pat {
+    match name {
+        Pat::Ident(Ident { sym, .. }) => Pat::Ident(quote_ident!(sym)),
+        Pat::Object(ObjectPat { props, .. }) => create_object_pat(props),
+        name => name,
+    }
+}
+
+/// create `props.$$slots || {}`
+pub fn create_slots_expr() -> Expr {
+    Expr::Bin(BinExpr {
+        span: DUMMY_SP,
+        op: BinaryOp::LogicalOr,
+        left: Box::new(Expr::Member(MemberExpr {
+            span: DUMMY_SP,
+            obj: ExprOrSuper::Expr(Box::new(Expr::Ident(quote_ident!("props")))),
+            prop: Box::new(Expr::Ident(quote_ident!("$$slots"))),
+            computed: false,
+        })),
+        right: Box::new(Expr::Object(ObjectLit {
+            span: DUMMY_SP,
+            props: vec![],
+        })),
+    })
+}
+
+pub fn create_let_stmt(name: Pat, init: Option<Expr>) -> Stmt {
+    Stmt::Decl(Decl::Var(VarDecl {
+        span: DUMMY_SP,
+        kind: VarDeclKind::Let,
+        declare: false,
+        decls: vec![VarDeclarator {
+            span: DUMMY_SP,
+            name,
+            init: init.map(Box::new),
+            definite: false,
+        }],
+    }))
+}
+
+pub fn create_object_pat(props: Vec<ObjectPatProp>) -> Pat {
+    Pat::Object(ObjectPat {
+        span: DUMMY_SP,
+        props,
+        optional: false,
+        type_ann: None,
+    })
+}
+
+/// create `(value) => { name = value }`
+fn create_assign_arrow(name: Pat) -> Expr {
+    Expr::Arrow(ArrowExpr {
+        span: DUMMY_SP,
+        params: vec![Pat::Ident(quote_ident!("value"))],
+        body: BlockStmtOrExpr::BlockStmt(BlockStmt {
+            span: DUMMY_SP,
+            stmts: vec![expr_to_stmt(Expr::Assign(AssignExpr {
+                span: DUMMY_SP,
+                op: AssignOp::Assign,
+                left: PatOrExpr::Pat(Box::new(name)),
+                right: Box::new(Expr::Ident(quote_ident!("value"))),
+            }))],
+        }),
+        is_async: false,
+        is_generator: false,
+        type_params: None,
+        return_type: None,
+    })
+}
+
+/// create `obj.prop`
+fn create_member_expr(obj: Expr, prop: &str) -> Expr {
+    Expr::Member(MemberExpr {
+        span: DUMMY_SP,
+        obj: ExprOrSuper::Expr(Box::new(obj)),
+        prop: Box::new(Expr::Ident(quote_ident!(prop))),
+        computed: false,
+    })
+}
+
+/// create `this.method(...args)`
+pub fn create_this_call(method: &str, args: Vec<ExprOrSpread>) -> Stmt {
+    expr_to_stmt(Expr::Call(CallExpr {
+        span: DUMMY_SP,
+        callee: ExprOrSuper::Expr(Box::new(Expr::Member(MemberExpr {
+            span: DUMMY_SP,
+            obj: ExprOrSuper::Expr(Box::new(Expr::This(ThisExpr { span: DUMMY_SP }))),
+            prop: Box::new(Expr::Ident(quote_ident!(method))),
+            computed: false,
+        }))),
+        args,
+        type_args: None,
+    }))
+}
+
+pub fn block_to_arrow(stmts: Vec<Stmt>) -> Expr {
+    Expr::Arrow(ArrowExpr {
+        span: DUMMY_SP,
+        params: vec![],
+        body: BlockStmtOrExpr::BlockStmt(BlockStmt {
+            span: DUMMY_SP,
+            stmts,
+        }),
+        is_async: false,
+        is_generator: false,
+        type_params: None,
+        return_type: None,
+    })
+}
+
+pub fn expr_to_arrow(expr: Expr) -> Expr {
     Expr::Arrow(ArrowExpr {
         span: DUMMY_SP,
         params: vec![],
@@ -374,7 +1924,7 @@ fn is_event_prop(name: PropName) -> bool {
     }
 }
 
-fn is_event_prop_name(name: &str) -> bool {
+pub fn is_event_prop_name(name: &str) -> bool {
     let mut chars = name.chars();
     if let (Some(c0), Some(c1), Some(c2)) = (chars.next(), chars.next(), chars.next()) {
         return c0 == 'o' && c1 == 'n' && c2 >= 'A' && c2 <= 'Z';
@@ -3,17 +3,26 @@
 mod css;
 mod identmap;
 mod jsx;
+mod ssr;
 mod statement;
 mod transformer;
+mod validator;
 mod walker;
 
-use crate::resolve::Resolver;
+use crate::{compiler::EmitOptions, resolve::Resolver};
 use std::{cell::RefCell, rc::Rc};
+use swc_common::SourceMap;
 use swc_ecma_visit::Fold;
 use transformer::ASTransformer;
 
-pub fn alef_transform(resolver: Rc<RefCell<Resolver>>) -> impl Fold {
+pub fn alef_transform(
+  resolver: Rc<RefCell<Resolver>>,
+  source_map: Rc<SourceMap>,
+  options: &EmitOptions,
+) -> impl Fold {
   ASTransformer {
     resolver: resolver.clone(),
+    source_map,
+    options: options.clone(),
   }
 }
@@ -0,0 +1,1082 @@
+// Copyright 2020-2021 postUI Lab. All rights reserved. MIT license.
+
+use super::{
+  css::CSS,
+  identmap::{get_idents_from_pat, IdentMap, IdentSet},
+  jsx::*,
+  statement::*,
+  transformer::{append_css_template, create_var_decl_stmt, extract_static_css, scope_styles},
+  validator::UNSUPPORTED_SPREAD_CHILD,
+};
+use crate::{compiler::EmitOptions, resolve::Resolver};
+use indexmap::IndexMap;
+use std::{
+  cell::{Cell, RefCell},
+  mem,
+  rc::Rc,
+};
+use swc_common::{errors::DiagnosticId, DUMMY_SP};
+use swc_ecma_ast::*;
+use swc_ecma_utils::{member_expr, quote_ident, quote_str, ExprFactory, HANDLER};
+use swc_ecma_visit::{noop_visit_type, Node, Visit, VisitWith};
+
+/// The elements that have no closing tag.
+const VOID_ELEMENTS: [&str; 14] = [
+  "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+  "track", "wbr",
+];
+
+/// The hydration markers, the client adopts the DOM nodes between the markers of the
+/// conditions, the lists, the components and the slots, and the text node after the text marker.
+pub const IF_MARKER: &str = "if";
+pub const LIST_MARKER: &str = "list";
+pub const COMPONENT_MARKER: &str = "c";
+pub const SLOT_MARKER: &str = "s";
+const TEXT_MARKER: &str = "<!--t-->";
+
+/// SSR Transformer for Alef Component, compiles the statements to the body of a render function
+/// `function App(props) { ...; return html }` which concatenates the HTML string instead of
+/// creating the DOM nodes.
+pub struct SSRTransformer {
+  pub resolver: Rc<RefCell<Resolver>>,
+  pub scope_idents: Rc<RefCell<IdentMap>>,
+  pub options: EmitOptions,
+}
+
+impl SSRTransformer {
+  pub fn transform(&self, mut statements: Vec<Statement>) -> (Vec<ImportDecl>, Vec<Stmt>) {
+    let specifier = self.resolver.borrow().specifier.clone();
+    let (async_states, deferred_stmts) = collect_async_states(&statements);
+    let async_id = if async_states.is_empty() {
+      None
+    } else {
+      Some(self.scope_idents.borrow_mut().create_ident("asyncId"))
+    };
+    let renderer = HTMLRenderer {
+      scope_idents: self.scope_idents.clone(),
+      style_scope: scope_styles(&mut statements, &specifier, self.options.is_dev),
+      contexts: statements
+        .iter()
+        .filter_map(|stmt| match stmt {
+          Statement::Const(ConstStatement {
+            name: Pat::Ident(Ident { sym, .. }),
+            typed: ConstTyped::Context,
+            init: Some(_),
+            ctx_name: Some(ctx_name),
+          }) => Some((ctx_name.clone(), quote_ident!(sym.clone()))),
+          _ => None,
+        })
+        .collect(),
+      async_states,
+      async_id: async_id.clone(),
+      deferring: Cell::new(false),
+      deferred: RefCell::new(vec![]),
+    };
+    let mut import_declare: Vec<ImportDecl> = vec![];
+    let mut stmts: Vec<Stmt> = vec![];
+    let mut async_stmts: Vec<Stmt> = vec![];
+    let mut styles = Html::default();
+    let mut html = Html::default();
+    let mut catch: Option<(Vec<Pat>, Html)> = None;
+
+    for (stmt, is_deferred) in statements.into_iter().zip(deferred_stmts) {
+      // the statements depending on the async states wait for them in the async render
+      let out = if is_deferred {
+        &mut async_stmts
+      } else {
+        &mut stmts
+      };
+      match stmt {
+        Statement::Import(ImportStatement {
+          specifiers, src, ..
+        }) => import_declare.push(ImportDecl {
+          span: DUMMY_SP,
+          specifiers,
+          src: quote_str!(src),
+          type_only: false,
+          asserts: None,
+        }),
+        Statement::Var(VarStatement { name, init, .. }) => {
+          out.push(create_var_decl_stmt(name, init, false))
+        }
+        Statement::Const(ConstStatement {
+          name,
+          typed,
+          init,
+          ctx_name,
+        }) => match typed {
+          ConstTyped::Regular => out.push(create_var_decl_stmt(name, init, true)),
+          // the memos are computed once on the server
+          ConstTyped::Memo => out.push(create_var_decl_stmt(name, init, false)),
+          ConstTyped::Prop => {
+            if let Some(props) = prop_to_pat_props(name, init) {
+              out.push(create_let_stmt(
+                create_object_pat(props),
+                Some(Expr::Ident(quote_ident!("props"))),
+              ));
+            }
+          }
+          ConstTyped::Slots => out.push(create_let_stmt(
+            strip_pat_type(name),
+            Some(create_slots_expr()),
+          )),
+          ConstTyped::Context => {
+            if let Some(ctx_name) = ctx_name {
+              out.push(renderer.render_context(name, ctx_name, init));
+            }
+          }
+        },
+        Statement::FC(FCStatement {
+          name,
+          scope_idents,
+          statements,
+        }) => {
+          let mut fc_idents = scope_idents;
+          fc_idents.inherit(&self.scope_idents.borrow());
+          let fc_idents = Rc::new(RefCell::new(fc_idents));
+          let fc_transformer = SSRTransformer {
+            resolver: self.resolver.clone(),
+            scope_idents: fc_idents.clone(),
+            options: self.options.clone(),
+          };
+          let (_, fc_stmts) = fc_transformer.transform(statements);
+          self
+            .scope_idents
+            .borrow_mut()
+            .merge_helpers(&fc_idents.borrow());
+          out.push(Stmt::Decl(Decl::Fn(FnDecl {
+            ident: name,
+            declare: false,
+            function: create_render_function(fc_stmts, false),
+          })));
+        }
+        // the side effects only run in the browser
+        Statement::SideEffect(_) => {}
+        Statement::Template(template) => renderer.render_template(template, &mut html),
+        Statement::Catch(CatchStatement {
+          params, template, ..
+        }) => {
+          let mut fallback = Html::default();
+          renderer.render_template(template, &mut fallback);
+          catch = Some((params, fallback));
+        }
+        // the parts depending on the async states are streamed instead of the fallbacks
+        Statement::Fallback(_) => {}
+        Statement::Style(StyleStatement { css }) => {
+          if !css.is_empty() && !extract_static_css(&self.resolver, &self.options, &css) {
+            renderer.render_style(&css, &mut styles);
+            append_css_template(&self.resolver, &css);
+          }
+        }
+        Statement::Export(_) => {}
+        Statement::Stmt(stmt) => out.push(stmt),
+      }
+    }
+
+    // const asyncId = suspend(props, async function* (asyncId) { ...; yield resolveAsync(asyncId, 0, html) })
+    if let Some(async_id) = async_id {
+      for (idx, part) in renderer.deferred.take().into_iter().enumerate() {
+        let chunk = renderer.call_helper(
+          "resolveAsync",
+          vec![
+            Expr::Ident(async_id.clone()).as_arg(),
+            Lit::Num(Number {
+              span: DUMMY_SP,
+              value: idx as f64,
+            })
+            .as_arg(),
+            part.as_arg(),
+          ],
+        );
+        async_stmts.push(expr_to_stmt(Expr::Yield(YieldExpr {
+          span: DUMMY_SP,
+          arg: Some(Box::new(chunk)),
+          delegate: false,
+        })));
+      }
+      let render = Expr::Fn(FnExpr {
+        ident: None,
+        function: Function {
+          params: vec![Param {
+            span: DUMMY_SP,
+            decorators: vec![],
+            pat: Pat::Ident(async_id.clone()),
+          }],
+          decorators: vec![],
+          span: DUMMY_SP,
+          body: Some(BlockStmt {
+            span: DUMMY_SP,
+            stmts: async_stmts,
+          }),
+          is_generator: true,
+          is_async: true,
+          type_params: None,
+          return_type: None,
+        },
+      });
+      let suspend = renderer.call_helper(
+        "suspend",
+        vec![Expr::Ident(quote_ident!("props")).as_arg(), render.as_arg()],
+      );
+      stmts.push(create_var_decl_stmt(
+        Pat::Ident(async_id),
+        Some(suspend),
+        true,
+      ));
+    }
+
+    // return `<style>...</style><div>...</div>`
+    let mut output = styles.clone();
+    output.append(html);
+    stmts.push(Stmt::Return(ReturnStmt {
+      span: DUMMY_SP,
+      arg: Some(Box::new(output.into_expr())),
+    }));
+
+    // try { ... } catch (error) { const reset = () => {}; return fallback }
+    if let Some((params, fallback)) = catch {
+      let mut params = params.into_iter().map(strip_pat_type);
+      let error = params.next();
+      let mut handler: Vec<Stmt> = params
+        .take(1)
+        .map(|reset| create_var_decl_stmt(reset, Some(block_to_arrow(vec![])), true))
+        .collect();
+      let mut output = styles;
+      output.append(fallback);
+      handler.push(Stmt::Return(ReturnStmt {
+        span: DUMMY_SP,
+        arg: Some(Box::new(output.into_expr())),
+      }));
+      stmts = vec![Stmt::Try(TryStmt {
+        span: DUMMY_SP,
+        block: BlockStmt {
+          span: DUMMY_SP,
+          stmts,
+        },
+        handler: Some(CatchClause {
+          span: DUMMY_SP,
+          param: error,
+          body: BlockStmt {
+            span: DUMMY_SP,
+            stmts: handler,
+          },
+        }),
+        finalizer: None,
+      })];
+    }
+
+    (import_declare, stmts)
+  }
+}
+
+/// create `function (props = {}) { ... }` or `function (props) { ... }`
+pub fn create_render_function(stmts: Vec<Stmt>, default_props: bool) -> Function {
+  let props = Pat::Ident(quote_ident!("props"));
+  Function {
+    params: vec![Param {
+      span: DUMMY_SP,
+      decorators: vec![],
+      pat: if default_props {
+        Pat::Assign(AssignPat {
+          span: DUMMY_SP,
+          left: Box::new(props),
+          right: Box::new(Expr::Object(ObjectLit {
+            span: DUMMY_SP,
+            props: vec![],
+          })),
+          type_ann: None,
+        })
+      } else {
+        props
+      },
+    }],
+    decorators: vec![],
+    span: DUMMY_SP,
+    body: Some(BlockStmt {
+      span: DUMMY_SP,
+      stmts,
+    }),
+    is_generator: false,
+    is_async: false,
+    type_params: None,
+    return_type: None,
+  }
+}
+
+/// A builder of the HTML string, the static parts are merged into string literals and joined
+/// with the dynamic parts by `+`.
+#[derive(Clone, Default)]
+struct Html {
+  parts: Vec<Expr>,
+  text: String,
+}
+
+impl Html {
+  fn push_str(&mut self, s: &str) {
+    self.text.push_str(s)
+  }
+
+  fn push_expr(&mut self, expr: Expr) {
+    self.flush();
+    self.parts.push(expr)
+  }
+
+  fn append(&mut self, other: Html) {
+    for part in other.parts {
+      match part {
+        Expr::Lit(Lit::Str(Str { value, .. })) => self.push_str(value.as_ref()),
+        part => self.push_expr(part),
+      }
+    }
+    self.push_str(&other.text);
+  }
+
+  /// push the dynamic block between the hydration markers, like `<!--if-->...<!--/if-->`
+  fn push_block(&mut self, marker: &str, expr: Expr) {
+    self.push_str(&format!("<!--{}-->", marker));
+    self.push_expr(expr);
+    self.push_str(&format!("<!--/{}-->", marker));
+  }
+
+  fn is_empty(&self) -> bool {
+    self.parts.is_empty() && self.text.is_empty()
+  }
+
+  fn flush(&mut self) {
+    if !self.text.is_empty() {
+      let text = mem::take(&mut self.text);
+      self.parts.push(Expr::Lit(Lit::Str(quote_str!(text))));
+    }
+  }
+
+  fn into_expr(mut self) -> Expr {
+    self.flush();
+    let mut parts = self.parts.into_iter();
+    match parts.next() {
+      Some(first) => parts.fold(first, |left, right| {
+        Expr::Bin(BinExpr {
+          span: DUMMY_SP,
+          op: BinaryOp::Add,
+          left: Box::new(left),
+          right: Box::new(right),
+        })
+      }),
+      None => Expr::Lit(Lit::Str(quote_str!(""))),
+    }
+  }
+}
+
+/// Render the templates to HTML.
+struct HTMLRenderer {
+  scope_idents: Rc<RefCell<IdentMap>>,
+  style_scope: Option<StyleScope>,
+  /// the contexts provided by the component, as `(name, ident)`
+  contexts: Vec<(String, Ident)>,
+  /// the states initialized by `await` and the variables derived from them
+  async_states: IdentSet,
+  /// the id of the async render, `None` if the component has no async state
+  async_id: Option<Ident>,
+  deferring: Cell<bool>,
+  /// the parts of the templates that are rendered after the async states are resolved
+  deferred: RefCell<Vec<Expr>>,
+}
+
+impl HTMLRenderer {
+  fn call_helper(&self, name: &str, args: Vec<ExprOrSpread>) -> Expr {
+    let ident = self.scope_idents.borrow_mut().create_ident(name);
+    Expr::Call(CallExpr {
+      span: DUMMY_SP,
+      callee: ExprOrSuper::Expr(Box::new(Expr::Ident(ident))),
+      args,
+      type_args: None,
+    })
+  }
+
+  /// check whether the node depends on the async states, the parts inside of a deferred
+  /// part are rendered with it
+  fn is_async<N>(&self, node: &N) -> bool
+  where
+    N: for<'a> VisitWith<AsyncStatesVisitor<'a>>,
+  {
+    self.async_id.is_some() && !self.deferring.get() && uses_async_states(node, &self.async_states)
+  }
+
+  /// render the part depending on the async states in the async render, the placeholder
+  /// `asyncPlaceholder(asyncId, 0)` is rendered instead which will be replaced by the
+  /// resolved chunk `resolveAsync(asyncId, 0, html)`
+  fn defer(&self, html: &mut Html, render: impl FnOnce(&mut Html)) {
+    let mut part = Html::default();
+    self.deferring.set(true);
+    render(&mut part);
+    self.deferring.set(false);
+    let idx = self.deferred.borrow().len();
+    self.deferred.borrow_mut().push(part.into_expr());
+    html.push_expr(self.call_helper(
+      "asyncPlaceholder",
+      vec![
+        Expr::Ident(self.async_id.clone().unwrap()).as_arg(),
+        Lit::Num(Number {
+          span: DUMMY_SP,
+          value: idx as f64,
+        })
+        .as_arg(),
+      ],
+    ));
+  }
+
+  /// check whether the expression is a slot declared by `Prop<Slots>`
+  fn is_slot(&self, expr: &Expr) -> bool {
+    let slotss = &self.scope_idents.borrow().slotss;
+    match expr {
+      Expr::Ident(Ident { sym, .. }) => slotss.contains(sym.as_ref()),
+      Expr::Member(MemberExpr {
+        obj: ExprOrSuper::Expr(obj),
+        computed: false,
+        ..
+      }) => match obj.as_ref() {
+        Expr::Ident(Ident { sym, .. }) => slotss.contains(sym.as_ref()),
+        _ => false,
+      },
+      _ => false,
+    }
+  }
+
+  /// render `<p class="title">{title}</p>` to `"<p class=\"title\">" + escape(title) + "</p>"`
+  fn render_element(&self, mut el: JSXElement, html: &mut Html) {
+    let is_component = self.scope_idents.borrow().is_component(&el.opening.name);
+    if (is_component && self.is_async(&el)) || self.is_async(&el.opening) {
+      self.defer(html, |html| self.render_element(el, html));
+      return;
+    }
+    if is_component {
+      html.push_block(COMPONENT_MARKER, self.render_component(el));
+      return;
+    }
+    let tag = match &el.opening.name {
+      JSXElementName::Ident(Ident { sym, .. }) => sym.to_string(),
+      _ => unreachable!(),
+    };
+    if tag.eq("slot") {
+      html.push_block(SLOT_MARKER, self.render_slot_element(el));
+      return;
+    }
+    if let Some(style_scope) = &self.style_scope {
+      style_scope.apply(&mut el.opening);
+    }
+    html.push_str(&format!("<{}", tag));
+    self.render_attrs(el.opening.attrs, html);
+    html.push_str(">");
+    if VOID_ELEMENTS.contains(&tag.as_str()) {
+      return;
+    }
+    for child in el.children {
+      self.render_child(child, html);
+    }
+    html.push_str(&format!("</{}>", tag));
+  }
+
+  fn render_template(&self, template: TemplateStatement, html: &mut Html) {
+    match template {
+      TemplateStatement::Element(el) => self.render_element(el, html),
+      TemplateStatement::Fragment(frag) => self.render_fragment(frag, html),
+      TemplateStatement::If(if_stmt) => self.render_condition_block(if_stmt, html),
+    }
+  }
+
+  fn render_fragment(&self, frag: JSXFragment, html: &mut Html) {
+    for child in frag.children {
+      self.render_child(child, html);
+    }
+  }
+
+  /// render `<Logo size={24}><img slot="icon" /></Logo>` to
+  /// `Logo({ size: 24, $$slots: { icon: () => "<img>" }, $$contexts: props.$$contexts })`
+  fn render_component(&self, el: JSXElement) -> Expr {
+    let mut slots: IndexMap<String, Html> = IndexMap::new();
+    for child in el.children {
+      match child {
+        JSXElementChild::JSXElement(mut el) => {
+          let name = match take_attr(&mut el, "slot") {
+            Some(Expr::Lit(Lit::Str(Str { value, .. }))) => value.as_ref().to_string(),
+            _ => "default".into(),
+          };
+          self.render_element(*el, slots.entry(name).or_default());
+        }
+        child => self.render_child(child, slots.entry("default".into()).or_default()),
+      }
+    }
+
+    let mut props: Vec<PropOrSpread> = el
+      .opening
+      .attrs
+      .into_iter()
+      .map(|attr| match attr {
+        JSXAttrOrSpread::JSXAttr(attr) => PropOrSpread::Prop(Box::new(attr_to_prop(attr))),
+        JSXAttrOrSpread::SpreadElement(spread) => PropOrSpread::Spread(spread),
+      })
+      .collect();
+    let slots: Vec<PropOrSpread> = slots
+      .into_iter()
+      .filter(|(_, html)| !html.is_empty())
+      .map(|(name, html)| {
+        PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
+          key: PropName::Ident(quote_ident!(name)),
+          value: Box::new(expr_to_arrow(html.into_expr())),
+        })))
+      })
+      .collect();
+    if !slots.is_empty() {
+      props.push(PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
+        key: PropName::Ident(quote_ident!("$$slots")),
+        value: Box::new(Expr::Object(ObjectLit {
+          span: DUMMY_SP,
+          props: slots,
+        })),
+      }))));
+    }
+    props.push(PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
+      key: PropName::Ident(quote_ident!("$$contexts")),
+      value: Box::new(self.create_contexts_expr()),
+    }))));
+    // pass the stream of the async renders to the descendants
+    props.push(PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
+      key: PropName::Ident(quote_ident!("$$stream")),
+      value: Box::new(Expr::Member(MemberExpr {
+        span: DUMMY_SP,
+        obj: ExprOrSuper::Expr(Box::new(Expr::Ident(quote_ident!("props")))),
+        prop: Box::new(Expr::Ident(quote_ident!("$$stream"))),
+        computed: false,
+      })),
+    }))));
+
+    Expr::Call(CallExpr {
+      span: DUMMY_SP,
+      callee: ExprOrSuper::Expr(jsx_name(el.opening.name)),
+      args: vec![Expr::Object(ObjectLit {
+        span: DUMMY_SP,
+        props,
+      })
+      .as_arg()],
+      type_args: None,
+    })
+  }
+
+  /// create `props.$$contexts`, or `Object.assign({}, props.$$contexts, { theme })` if the
+  /// component provides contexts to the descendants
+  fn create_contexts_expr(&self) -> Expr {
+    let contexts = create_contexts_member();
+    if self.contexts.is_empty() {
+      return contexts;
+    }
+    Expr::Call(CallExpr {
+      span: DUMMY_SP,
+      callee: member_expr!(DUMMY_SP, Object.assign).as_callee(),
+      args: vec![
+        Expr::Object(ObjectLit {
+          span: DUMMY_SP,
+          props: vec![],
+        })
+        .as_arg(),
+        contexts.as_arg(),
+        Expr::Object(ObjectLit {
+          span: DUMMY_SP,
+          props: self
+            .contexts
+            .iter()
+            .map(|(name, ident)| {
+              PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
+                key: PropName::Str(quote_str!(name.as_str())),
+                value: Box::new(Expr::Ident(ident.clone())),
+              })))
+            })
+            .collect(),
+        })
+        .as_arg(),
+      ],
+      type_args: None,
+    })
+  }
+
+  /// render `const theme: Context<"theme"> = "light"` to `let theme = "light"`, and a context
+  /// without initializer to `let theme = (props.$$contexts || {})["theme"]`
+  fn render_context(&self, name: Pat, ctx_name: String, init: Option<Expr>) -> Stmt {
+    match (name, init) {
+      (Pat::Ident(Ident { sym, .. }), Some(init)) => {
+        create_let_stmt(Pat::Ident(quote_ident!(sym)), Some(init))
+      }
+      (name, _) => create_let_stmt(
+        strip_pat_type(name),
+        Some(Expr::Member(MemberExpr {
+          span: DUMMY_SP,
+          obj: ExprOrSuper::Expr(Box::new(Expr::Paren(ParenExpr {
+            span: DUMMY_SP,
+            expr: Box::new(Expr::Bin(BinExpr {
+              span: DUMMY_SP,
+              op: BinaryOp::LogicalOr,
+              left: Box::new(create_contexts_member()),
+              right: Box::new(Expr::Object(ObjectLit {
+                span: DUMMY_SP,
+                props: vec![],
+              })),
+            })),
+          }))),
+          prop: Box::new(Expr::Lit(Lit::Str(quote_str!(ctx_name)))),
+          computed: true,
+        })),
+      ),
+    }
+  }
+
+  /// render `<slot name="icon"><img /></slot>` to
+  /// `renderSlot((props.$$slots || {}).icon, () => "<img>")`
+  fn render_slot_element(&self, mut el: JSXElement) -> Expr {
+    let name = match take_attr(&mut el, "name") {
+      Some(Expr::Lit(Lit::Str(Str { value, .. }))) => value.as_ref().to_string(),
+      _ => "default".into(),
+    };
+    let slot = Expr::Member(MemberExpr {
+      span: DUMMY_SP,
+      obj: ExprOrSuper::Expr(Box::new(Expr::Paren(ParenExpr {
+        span: DUMMY_SP,
+        expr: Box::new(create_slots_expr()),
+      }))),
+      prop: Box::new(Expr::Ident(quote_ident!(name))),
+      computed: false,
+    });
+    let mut fallback = Html::default();
+    for child in el.children {
+      self.render_child(child, &mut fallback);
+    }
+    self.create_slot(
+      slot,
+      if fallback.is_empty() {
+        None
+      } else {
+        Some(fallback.into_expr())
+      },
+    )
+  }
+
+  /// render `{slots.icon || <img />}` to `renderSlot(slots.icon, () => "<img>")`,
+  /// returns the expression back if it is not a slot
+  fn render_slot_expr(&self, expr: Expr) -> Result<Expr, Box<Expr>> {
+    match expr {
+      Expr::Bin(BinExpr {
+        op: BinaryOp::LogicalOr,
+        left,
+        right,
+        ..
+      })
+      | Expr::Bin(BinExpr {
+        op: BinaryOp::NullishCoalescing,
+        left,
+        right,
+        ..
+      }) if self.is_slot(left.as_ref()) => {
+        let mut fallback = Html::default();
+        self.render_node(*right, &mut fallback);
+        Ok(self.create_slot(*left, Some(fallback.into_expr())))
+      }
+      expr if self.is_slot(&expr) => Ok(self.create_slot(expr, None)),
+      expr => Err(Box::new(expr)),
+    }
+  }
+
+  fn create_slot(&self, slot: Expr, fallback: Option<Expr>) -> Expr {
+    let mut args = vec![slot.as_arg()];
+    if let Some(fallback) = fallback {
+      args.push(expr_to_arrow(fallback).as_arg());
+    }
+    self.call_helper("renderSlot", args)
+  }
+
+  fn render_condition_block(&self, if_stmt: IfStmt, html: &mut Html) {
+    if self.is_async(&if_stmt) {
+      self.defer(html, |html| self.render_condition_block(if_stmt, html));
+      return;
+    }
+    html.push_block(IF_MARKER, self.render_condition(if_stmt))
+  }
+
+  /// render `if (a) <p /> else <span />` to `a ? "<p></p>" : "<span></span>"`
+  fn render_condition(&self, if_stmt: IfStmt) -> Expr {
+    condition_steps(if_stmt).into_iter().rev().fold(
+      Expr::Lit(Lit::Str(quote_str!(""))),
+      |alt, (test, branch)| {
+        let cons = self.render_branch(branch);
+        match test {
+          Some(test) => Expr::Cond(CondExpr {
+            span: DUMMY_SP,
+            test: Box::new(test),
+            cons: Box::new(cons),
+            alt: Box::new(alt),
+          }),
+          None => cons,
+        }
+      },
+    )
+  }
+
+  /// render the branch of the condition, a block branch with statements is wrapped by
+  /// an IIFE `(() => { ...; return html })()`
+  fn render_branch(&self, stmt: Stmt) -> Expr {
+    let is_block = matches!(stmt, Stmt::Block(_));
+    let stmts = match stmt {
+      Stmt::Block(BlockStmt { stmts, .. }) => stmts,
+      stmt => vec![stmt],
+    };
+    let mut body: Vec<Stmt> = vec![];
+    let mut html = Html::default();
+    for stmt in stmts {
+      match stmt {
+        Stmt::Expr(ExprStmt { expr, .. }) if !is_block || is_jsx(expr.as_ref()) => {
+          self.render_node(*expr, &mut html)
+        }
+        stmt => body.push(stmt),
+      }
+    }
+    if body.is_empty() {
+      return html.into_expr();
+    }
+    body.push(Stmt::Return(ReturnStmt {
+      span: DUMMY_SP,
+      arg: Some(Box::new(html.into_expr())),
+    }));
+    Expr::Call(CallExpr {
+      span: DUMMY_SP,
+      callee: block_to_arrow(body).wrap_with_paren().as_callee(),
+      args: vec![],
+      type_args: None,
+    })
+  }
+
+  /// render `todos.map(todo => <li>{todo}</li>)` to
+  /// `todos.map(todo => "<li>" + escape(todo) + "</li>").join("")`
+  fn render_list(&self, list: ListExpr) -> Expr {
+    let ListExpr {
+      items,
+      params,
+      mut stmts,
+      node,
+    } = list;
+    let (_, node) = take_key(node);
+    let mut html = Html::default();
+    self.render_node(node, &mut html);
+    let body = if stmts.is_empty() {
+      BlockStmtOrExpr::Expr(Box::new(html.into_expr()))
+    } else {
+      stmts.push(Stmt::Return(ReturnStmt {
+        span: DUMMY_SP,
+        arg: Some(Box::new(html.into_expr())),
+      }));
+      BlockStmtOrExpr::BlockStmt(BlockStmt {
+        span: DUMMY_SP,
+        stmts,
+      })
+    };
+    let map = Expr::Call(CallExpr {
+      span: DUMMY_SP,
+      callee: ExprOrSuper::Expr(Box::new(Expr::Member(MemberExpr {
+        span: DUMMY_SP,
+        obj: ExprOrSuper::Expr(Box::new(items)),
+        prop: Box::new(Expr::Ident(quote_ident!("map"))),
+        computed: false,
+      }))),
+      args: vec![Expr::Arrow(ArrowExpr {
+        span: DUMMY_SP,
+        params,
+        body,
+        is_async: false,
+        is_generator: false,
+        type_params: None,
+        return_type: None,
+      })
+      .as_arg()],
+      type_args: None,
+    });
+    Expr::Call(CallExpr {
+      span: DUMMY_SP,
+      callee: ExprOrSuper::Expr(Box::new(Expr::Member(MemberExpr {
+        span: DUMMY_SP,
+        obj: ExprOrSuper::Expr(Box::new(map)),
+        prop: Box::new(Expr::Ident(quote_ident!("join"))),
+        computed: false,
+      }))),
+      args: vec![Lit::Str(quote_str!("")).as_arg()],
+      type_args: None,
+    })
+  }
+
+  /// render a JSX node or an expression, the value of the expression is escaped
+  fn render_node(&self, expr: Expr, html: &mut Html) {
+    match unwrap_paren(expr) {
+      Expr::JSXElement(el) => self.render_element(*el, html),
+      Expr::JSXFragment(frag) => self.render_fragment(frag, html),
+      expr => match expr_to_list(expr) {
+        Ok(list) => html.push_block(LIST_MARKER, self.render_list(list)),
+        Err(expr) => {
+          html.push_str(TEXT_MARKER);
+          html.push_expr(self.call_helper("escape", vec![expr.as_arg()]))
+        }
+      },
+    }
+  }
+
+  fn render_child(&self, child: JSXElementChild, html: &mut Html) {
+    match child {
+      JSXElementChild::JSXText(text) => {
+        html.push_str(&escape_html(&jsx_text_to_string(text.value.as_ref())))
+      }
+      JSXElementChild::JSXElement(el) => self.render_element(*el, html),
+      JSXElementChild::JSXFragment(frag) => self.render_fragment(frag, html),
+      JSXElementChild::JSXExprContainer(JSXExprContainer { expr, .. }) => match expr {
+        JSXExpr::Expr(e) => self.render_expr_child(*e, html),
+        JSXExpr::JSXEmptyExpr(..) => {}
+      },
+      JSXElementChild::JSXSpreadChild(JSXSpreadChild { span, .. }) => HANDLER.with(|handler| {
+        handler
+          .struct_span_err(span, "JSX spread children are not supported")
+          .code(DiagnosticId::Error(UNSUPPORTED_SPREAD_CHILD.into()))
+          .help("render the items with `.map()` instead")
+          .emit()
+      }),
+    }
+  }
+
+  fn render_expr_child(&self, expr: Expr, html: &mut Html) {
+    if self.is_async(&expr) {
+      self.defer(html, |html| self.render_expr_child(expr, html));
+      return;
+    }
+    match self.render_slot_expr(unwrap_paren(expr)) {
+      Ok(slot) => html.push_block(SLOT_MARKER, slot),
+      Err(expr) => match expr_to_if_stmt(*expr) {
+        Ok(if_stmt) => html.push_block(IF_MARKER, self.render_condition(if_stmt)),
+        Err(expr) => self.render_node(*expr, html),
+      },
+    }
+  }
+
+  /// render the attributes, the static values are escaped at compile time, and the dynamic
+  /// values are rendered by `attr(name, value)` and `spreadAttrs(props)` at runtime
+  fn render_attrs(&self, attrs: Vec<JSXAttrOrSpread>, html: &mut Html) {
+    for attr in attrs {
+      let JSXAttr { name, value, .. } = match attr {
+        JSXAttrOrSpread::JSXAttr(attr) => attr,
+        JSXAttrOrSpread::SpreadElement(SpreadElement { expr, .. }) => {
+          html.push_expr(self.call_helper("spreadAttrs", vec![expr.as_arg()]));
+          continue;
+        }
+      };
+      let name = match name {
+        JSXAttrName::Ident(Ident { sym, .. }) => match sym.as_ref() {
+          "className" => "class".into(),
+          "htmlFor" => "for".into(),
+          name => name.to_string(),
+        },
+        // the transitions are played in the browser only
+        JSXAttrName::JSXNamespacedName(JSXNamespacedName { ns, .. })
+          if is_transition_directive(ns.sym.as_ref()) =>
+        {
+          continue
+        }
+        JSXAttrName::JSXNamespacedName(JSXNamespacedName { ns, name }) => {
+          format!("{}:{}", ns.sym, name.sym)
+        }
+      };
+      if name.eq("key") || name.eq("ref") || is_event_prop_name(&name) {
+        continue;
+      }
+      let value = match value {
+        None => {
+          html.push_str(&format!(" {}", name));
+          continue;
+        }
+        Some(JSXAttrValue::Lit(lit)) => Expr::Lit(lit),
+        Some(JSXAttrValue::JSXExprContainer(JSXExprContainer {
+          expr: JSXExpr::Expr(expr),
+          ..
+        })) => unwrap_paren(*expr),
+        Some(_) => continue,
+      };
+      match value {
+        Expr::Lit(Lit::Str(Str { value, .. })) => {
+          html.push_str(&format!(" {}=\"{}\"", name, escape_html(value.as_ref())))
+        }
+        Expr::Lit(Lit::Num(Number { value, .. })) => {
+          html.push_str(&format!(" {}=\"{}\"", name, value))
+        }
+        Expr::Lit(Lit::Bool(Bool { value, .. })) => {
+          if value {
+            html.push_str(&format!(" {}", name))
+          }
+        }
+        Expr::Lit(Lit::Null(_)) => {}
+        expr => html.push_expr(self.call_helper(
+          "attr",
+          vec![Lit::Str(quote_str!(name)).as_arg(), expr.as_arg()],
+        )),
+      }
+    }
+  }
+
+  /// render the scoped style to `<style id="alef-xxx">...</style>`, the interpolated values are
+  /// escaped by `escapeStyle(value)` so they can't close the `<style>` element
+  fn render_style(&self, css: &CSS, html: &mut Html) {
+    let id = match &self.style_scope {
+      Some(StyleScope { class_name, .. }) => class_name.clone(),
+      None => return,
+    };
+    html.push_str(&format!("<style id=\"{}\">", id));
+    let template = css.to_template();
+    if template.exprs.is_empty() {
+      html.push_str(&template.quasis.concat());
+    } else {
+      let mut tpl = css.to_tpl();
+      tpl.exprs = tpl
+        .exprs
+        .into_iter()
+        .map(|expr| Box::new(self.call_helper("escapeStyle", vec![expr.as_arg()])))
+        .collect();
+      html.push_expr(Expr::Tpl(tpl));
+    }
+    html.push_str("</style>");
+  }
+}
+
+/// collect the states initialized by `let data = await ...` and the variables derived from them,
+/// returns the async states and whether each statement depends on them
+fn collect_async_states(statements: &[Statement]) -> (IdentSet, Vec<bool>) {
+  let mut async_states = IdentSet::new();
+  let deferred = statements
+    .iter()
+    .map(|stmt| {
+      let (idents, is_async) = match stmt {
+        Statement::Var(VarStatement {
+          name,
+          is_async: true,
+          ..
+        }) => (get_idents_from_pat(name), true),
+        Statement::Var(VarStatement { name, init, .. })
+        | Statement::Const(ConstStatement {
+          name,
+          init,
+          typed: ConstTyped::Regular,
+          ..
+        })
+        | Statement::Const(ConstStatement {
+          name,
+          init,
+          typed: ConstTyped::Memo,
+          ..
+        }) => (
+          get_idents_from_pat(name),
+          match init {
+            Some(init) => uses_async_states(init, &async_states),
+            None => false,
+          },
+        ),
+        Statement::Stmt(stmt) => (
+          match stmt {
+            Stmt::Decl(Decl::Fn(FnDecl { ident, .. })) => vec![ident.clone()],
+            _ => vec![],
+          },
+          uses_async_states(stmt, &async_states),
+        ),
+        _ => (vec![], false),
+      };
+      if is_async {
+        for ident in idents {
+          async_states.insert(ident.sym.as_ref().to_string());
+        }
+      }
+      is_async
+    })
+    .collect();
+  (async_states, deferred)
+}
+
+fn uses_async_states<'a, N>(node: &N, async_states: &'a IdentSet) -> bool
+where
+  N: VisitWith<AsyncStatesVisitor<'a>>,
+{
+  let mut visitor = AsyncStatesVisitor {
+    async_states,
+    found: false,
+  };
+  node.visit_with(&Invalid { span: DUMMY_SP } as _, &mut visitor);
+  visitor.found
+}
+
+/// A visitor to check whether a node uses the async states.
+struct AsyncStatesVisitor<'a> {
+  async_states: &'a IdentSet,
+  found: bool,
+}
+
+impl<'a> Visit for AsyncStatesVisitor<'a> {
+  noop_visit_type!();
+
+  fn visit_ident(&mut self, id: &Ident, _: &dyn Node) {
+    if self.async_states.contains(id.sym.as_ref()) {
+      self.found = true;
+    }
+  }
+
+  fn visit_member_expr(&mut self, expr: &MemberExpr, _: &dyn Node) {
+    expr.obj.visit_with(expr as _, self);
+    if expr.computed {
+      expr.prop.visit_with(expr as _, self);
+    }
+  }
+
+  fn visit_prop_name(&mut self, name: &PropName, _: &dyn Node) {
+    if let PropName::Computed(computed) = name {
+      computed.visit_with(name as _, self);
+    }
+  }
+
+  fn visit_jsx_element_name(&mut self, _: &JSXElementName, _: &dyn Node) {}
+
+  fn visit_jsx_attr_name(&mut self, _: &JSXAttrName, _: &dyn Node) {}
+}
+
+/// create `props.$$contexts`
+fn create_contexts_member() -> Expr {
+  Expr::Member(MemberExpr {
+    span: DUMMY_SP,
+    obj: ExprOrSuper::Expr(Box::new(Expr::Ident(quote_ident!("props")))),
+    prop: Box::new(Expr::Ident(quote_ident!("$$contexts"))),
+    computed: false,
+  })
+}
+
+/// escape the HTML special characters
+fn escape_html(s: &str) -> String {
+  let mut escaped = String::with_capacity(s.len());
+  for c in s.chars() {
+    match c {
+      '&' => escaped.push_str("&amp;"),
+      '<' => escaped.push_str("&lt;"),
+      '>' => escaped.push_str("&gt;"),
+      '"' => escaped.push_str("&quot;"),
+      '\'' => escaped.push_str("&#39;"),
+      c => escaped.push(c),
+    }
+  }
+  escaped
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_escape_html() {
+    assert_eq!(
+      escape_html("a < b && c > \"d\""),
+      "a &lt; b &amp;&amp; c &gt; &quot;d&quot;"
+    );
+    assert_eq!(escape_html("it's"), "it&#39;s");
+  }
+}
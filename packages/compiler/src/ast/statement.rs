@@ -1,6 +1,7 @@
 // Copyright 2020-2021 postUI Lab. All rights reserved. MIT license.
 
 use super::{css::CSS, identmap::IdentMap};
+use swc_common::Span;
 use swc_ecma_ast::*;
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -32,7 +33,7 @@ pub enum ConstTyped {
 pub struct ConstStatement {
     pub name: Pat,
     pub typed: ConstTyped,
-    pub init: Expr,
+    pub init: Option<Expr>,
     pub ctx_name: Option<String>,
 }
 
@@ -56,6 +57,26 @@ pub enum TemplateStatement {
     If(IfStmt),
 }
 
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AsyncStatus {
+    Pending,  // match `$t_pending:`
+    Rejected, // match `$t_rejected:`
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FallbackStatement {
+    pub span: Span,
+    pub status: AsyncStatus,
+    pub template: TemplateStatement,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CatchStatement {
+    pub span: Span,
+    pub params: Vec<Pat>, // the params of the fallback, like `(error, reset)`
+    pub template: TemplateStatement,
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct StyleStatement {
     pub css: CSS,
@@ -74,6 +95,8 @@ pub enum Statement {
     FC(FCStatement),                 // match `FC<Props>`
     SideEffect(SideEffectStatement), // match `$:` and `$_{NAME}:`
     Template(TemplateStatement),     // match `$t:`
+    Fallback(FallbackStatement),     // match `$t_pending:` and `$t_rejected:`
+    Catch(CatchStatement),           // match `$catch:`
     Style(StyleStatement),           // match `$style:`
     Export(ExportStatement),         // match `export default { ... }`
     Stmt(Stmt),                      // regular statement
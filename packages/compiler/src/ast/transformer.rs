@@ -1,16 +1,29 @@
 // Copyright 2020-2021 postUI Lab. All rights reserved. MIT license.
 
-use super::{identmap::IdentMap, jsx::JSXTransformer, statement::*, walker::ASTWalker};
-use crate::resolve::{to_component_name, Resolver};
+use super::{
+  css::CSS,
+  identmap::IdentMap,
+  jsx::{create_this_call, expr_to_arrow, ElementShapeCollector, JSXTransformer, StyleScope},
+  ssr::{create_render_function, SSRTransformer},
+  statement::*,
+  validator::{validate, UNUSED_CSS},
+  walker::ASTWalker,
+};
+use crate::{
+  compiler::EmitOptions,
+  resolve::{to_component_name, to_style_id, ExtractedCSS, Resolver},
+};
 use std::{cell::RefCell, iter, path::Path, rc::Rc};
-use swc_common::DUMMY_SP;
+use swc_common::{errors::DiagnosticId, SourceMap, DUMMY_SP};
 use swc_ecma_ast::*;
-use swc_ecma_utils::{quote_ident, ExprFactory};
-use swc_ecma_visit::{noop_fold_type, Fold};
+use swc_ecma_utils::{member_expr, quote_ident, quote_str, ExprFactory, HANDLER};
+use swc_ecma_visit::{noop_fold_type, Fold, VisitWith};
 
 /// AST Transformer for Alef Component.
 pub struct ASTransformer {
   pub resolver: Rc<RefCell<Resolver>>,
+  pub source_map: Rc<SourceMap>,
+  pub options: EmitOptions,
 }
 
 impl Fold for ASTransformer {
@@ -19,13 +32,36 @@ impl Fold for ASTransformer {
   fn fold_module_items(&mut self, items: Vec<ModuleItem>) -> Vec<ModuleItem> {
     let mut walker = ASTWalker::new();
     let statements = walker.walk(items);
-    let helper_component_id = walker.scope_idents.create_ident("Component");
+    validate(&statements);
+    let helper_component_id = if self.options.ssr {
+      None
+    } else {
+      Some(walker.scope_idents.create_ident("Component"))
+    };
+    let helper_refresh_id = if self.options.hot_refresh && !self.options.ssr {
+      Some(walker.scope_idents.create_ident("Refresh"))
+    } else {
+      None
+    };
+    let mut fc_names: Vec<String> = vec![];
+    collect_fc_names(&statements, &mut fc_names);
     let scope_idents = Rc::new(RefCell::new(walker.scope_idents));
-    let transformer = StatementsTransformer {
-      resolver: self.resolver.clone(),
-      scope_idents: scope_idents.clone(),
+    let (import_declare, stmts) = if self.options.ssr {
+      let transformer = SSRTransformer {
+        resolver: self.resolver.clone(),
+        scope_idents: scope_idents.clone(),
+        options: self.options.clone(),
+      };
+      transformer.transform(statements)
+    } else {
+      let transformer = StatementsTransformer {
+        resolver: self.resolver.clone(),
+        scope_idents: scope_idents.clone(),
+        source_map: self.source_map.clone(),
+        options: self.options.clone(),
+      };
+      transformer.transform(statements)
     };
-    let (import_declare, stmts) = transformer.transform(statements);
     let mut resolver = self.resolver.borrow_mut();
     let scope_idents = scope_idents.borrow();
     let mut output: Vec<ModuleItem> = vec![];
@@ -102,43 +138,54 @@ impl Fold for ASTransformer {
       output.push(ModuleItem::ModuleDecl(ModuleDecl::Import(import)));
     }
 
-    // export component class
+    // export component class, or the render function in SSR mode
     {
       let path = Path::new(resolver.specifier.as_str());
       let file_name = path.file_name().as_ref().unwrap().to_str().unwrap();
       let name = to_component_name(file_name);
-      output.push(ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultExpr(
-        ExportDefaultExpr {
+      let display_name_stmt = if self.options.is_dev && !self.options.ssr {
+        Some(create_display_name_stmt(quote_ident!(name.clone())))
+      } else {
+        None
+      };
+      let refresh_stmts = helper_refresh_id.map(|helper_refresh_id| {
+        (
+          create_refresh_stmt(
+            helper_refresh_id.clone(),
+            quote_ident!(name.clone()),
+            &resolver.specifier,
+            fc_names,
+          ),
+          create_hot_accept_stmt(helper_refresh_id, &resolver.specifier),
+        )
+      });
+      // export a declaration to bind the name in the module scope
+      let decl = match helper_component_id {
+        Some(helper_component_id) => DefaultDecl::Class(ClassExpr {
+          ident: Some(quote_ident!(name)),
+          class: create_component_class(helper_component_id, stmts),
+        }),
+        None => DefaultDecl::Fn(FnExpr {
+          ident: Some(quote_ident!(name)),
+          function: create_render_function(stmts, true),
+        }),
+      };
+      output.push(ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultDecl(
+        ExportDefaultDecl {
           span: DUMMY_SP,
-          expr: Box::new(Expr::Class(ClassExpr {
-            ident: Some(quote_ident!(name)),
-            class: Class {
-              span: DUMMY_SP,
-              decorators: vec![],
-              body: vec![ClassMember::Constructor(Constructor {
-                span: DUMMY_SP,
-                key: PropName::Ident(quote_ident!("constructor")),
-                params: vec![ParamOrTsParamProp::Param(Param {
-                  span: DUMMY_SP,
-                  decorators: vec![],
-                  pat: Pat::Ident(quote_ident!("props")),
-                })],
-                body: Some(BlockStmt {
-                  span: DUMMY_SP,
-                  stmts,
-                }),
-                accessibility: None,
-                is_optional: false,
-              })],
-              super_class: Some(Box::new(Expr::Ident(helper_component_id))),
-              is_abstract: false,
-              type_params: None,
-              super_type_params: None,
-              implements: vec![],
-            },
-          })),
+          decl,
         },
       )));
+      // App.displayName = "App"
+      if let Some(display_name_stmt) = display_name_stmt {
+        output.push(ModuleItem::Stmt(display_name_stmt));
+      }
+      // Refresh(App, "./App.alef", ["Counter"])
+      // if (import.meta.hot) { import.meta.hot.accept((mod) => { ... }) }
+      if let Some((refresh_stmt, hot_accept_stmt)) = refresh_stmts {
+        output.push(ModuleItem::Stmt(refresh_stmt));
+        output.push(ModuleItem::Stmt(hot_accept_stmt));
+      }
     }
 
     // store dependency graph
@@ -148,21 +195,136 @@ impl Fold for ASTransformer {
   }
 }
 
+/// Scope the `$style` sheets with the hash of the specifier, returns `None` if there is no style.
+/// The unused rules and the dropped selectors are warned, the unused rules are stripped in
+/// production.
+pub fn scope_styles(
+  statements: &mut [Statement],
+  specifier: &str,
+  is_dev: bool,
+) -> Option<StyleScope> {
+  let mut collector = ElementShapeCollector::default();
+  for stmt in statements.iter() {
+    let template = match stmt {
+      Statement::Template(template) => template,
+      Statement::Fallback(FallbackStatement { template, .. }) => template,
+      Statement::Catch(CatchStatement { template, .. }) => template,
+      _ => continue,
+    };
+    match template {
+      TemplateStatement::Element(el) => el.visit_children_with(&mut collector),
+      TemplateStatement::Fragment(frag) => frag.visit_children_with(&mut collector),
+      TemplateStatement::If(if_stmt) => if_stmt.visit_children_with(&mut collector),
+    }
+  }
+  let mut style_scope: Option<StyleScope> = None;
+  for stmt in statements.iter_mut() {
+    if let Statement::Style(StyleStatement { css }) = stmt {
+      let class_name = to_style_id(specifier);
+      let (compounds, unused) = css.scope(&class_name, &collector.shapes);
+      for (selectors, span) in unused {
+        HANDLER.with(|handler| {
+          handler
+            .struct_span_warn(span, &format!("unused CSS selector `{}`", selectors))
+            .code(DiagnosticId::Lint(UNUSED_CSS.into()))
+            .help("no element in the templates can match it, remove the selector")
+            .emit()
+        });
+      }
+      if !is_dev {
+        css.strip_unused();
+      }
+      style_scope
+        .get_or_insert_with(|| StyleScope {
+          class_name,
+          compounds: vec![],
+        })
+        .compounds
+        .extend(compounds);
+    }
+  }
+  style_scope
+}
+
+/// Append the stylesheet to `Resolver.css`, the styles of the inline components are collected
+/// with the styles of the module.
+pub fn append_css_template(resolver: &Rc<RefCell<Resolver>>, css: &CSS) {
+  let template = css.to_template();
+  let mut resolver = resolver.borrow_mut();
+  match resolver.css.as_mut() {
+    Some(existing) => existing.append(template),
+    None => resolver.css = Some(template),
+  }
+}
+
+/// Extract the static stylesheet into `Resolver.extracted_css` if the `extract_css` option is
+/// enabled, returns `false` if the stylesheet has expressions that must be evaluated at runtime.
+pub fn extract_static_css(
+  resolver: &Rc<RefCell<Resolver>>,
+  options: &EmitOptions,
+  css: &CSS,
+) -> bool {
+  if !options.extract_css || !css.to_template().exprs.is_empty() {
+    return false;
+  }
+  let (code, mappings) = css.to_css();
+  let mut resolver = resolver.borrow_mut();
+  match resolver.extracted_css.as_mut() {
+    Some(extracted) => extracted.append(&code, mappings),
+    None => {
+      resolver.extracted_css = Some(ExtractedCSS {
+        code,
+        map: None,
+        mappings,
+      })
+    }
+  }
+  true
+}
+
 pub struct StatementsTransformer {
   pub resolver: Rc<RefCell<Resolver>>,
   pub scope_idents: Rc<RefCell<IdentMap>>,
+  pub source_map: Rc<SourceMap>,
+  pub options: EmitOptions,
 }
 
 impl StatementsTransformer {
-  pub fn transform(&self, statements: Vec<Statement>) -> (Vec<ImportDecl>, Vec<Stmt>) {
+  pub fn transform(&self, mut statements: Vec<Statement>) -> (Vec<ImportDecl>, Vec<Stmt>) {
+    let specifier = self.resolver.borrow().specifier.clone();
     let jsx_transformer = JSXTransformer {
       resolver: self.resolver.clone(),
       scope_idents: self.scope_idents.clone(),
+      style_scope: scope_styles(&mut statements, &specifier, self.options.is_dev),
+      source_map: self.source_map.clone(),
+      is_dev: self.options.is_dev,
+      cursor: if self.options.hydrate {
+        Some(self.scope_idents.borrow_mut().create_ident("cursor"))
+      } else {
+        None
+      },
     };
     let mut import_declare: Vec<ImportDecl> = vec![];
     let mut export_default: Option<Expr> = None;
     let mut stmts: Vec<Stmt> = vec![];
     let mut nodes: Vec<Expr> = vec![];
+    let mut styles: Vec<Expr> = vec![];
+    let mut hot_states: Vec<Ident> = vec![];
+    let restored_ident = if self.options.hot_refresh
+      && statements.iter().any(|stmt| {
+        matches!(
+          stmt,
+          Statement::Var(VarStatement {
+            name: Pat::Ident(_),
+            is_async: false,
+            ..
+          })
+        )
+      }) {
+      Some(self.scope_idents.borrow_mut().create_ident("state"))
+    } else {
+      None
+    };
 
     // insert 'super(props)'
     {
@@ -180,8 +342,30 @@ impl StatementsTransformer {
       }))
     }
 
+    // const state = this.restore()
+    if let Some(restored_ident) = &restored_ident {
+      stmts.push(create_var_decl_stmt(
+        Pat::Ident(restored_ident.clone()),
+        Some(Expr::Call(CallExpr {
+          span: DUMMY_SP,
+          callee: member_expr!(DUMMY_SP, this.restore).as_callee(),
+          args: vec![],
+          type_args: None,
+        })),
+        true,
+      ));
+    }
+
+    let catch_index = stmts.len();
     for stmt in statements {
       match stmt {
+        // register the error boundary before any child component, memo or effect is created
+        Statement::Catch(CatchStatement {
+          params, template, ..
+        }) => stmts.insert(
+          catch_index,
+          jsx_transformer.transform_catch(params, template),
+        ),
         Statement::Import(ImportStatement {
           specifiers, src, ..
         }) => import_declare.push(ImportDecl {
@@ -196,6 +380,22 @@ impl StatementsTransformer {
           type_only: false,
           asserts: None,
         }),
+        Statement::Var(VarStatement {
+          name,
+          init: Some(Expr::Await(AwaitExpr { arg, .. })),
+          is_async: true,
+          ..
+        }) => stmts.extend(jsx_transformer.transform_async_state(name, *arg)),
+        // let count = "count" in state ? state.count : 0
+        Statement::Var(VarStatement {
+          name: Pat::Ident(ident),
+          init,
+          ..
+        }) if restored_ident.is_some() => {
+          let init = create_restored_expr(restored_ident.clone().unwrap(), &ident, init);
+          hot_states.push(ident.clone());
+          stmts.push(create_var_decl_stmt(Pat::Ident(ident), Some(init), false))
+        }
         Statement::Var(VarStatement { name, init, .. }) => {
           stmts.push(create_var_decl_stmt(name, init, false))
         }
@@ -206,19 +406,71 @@ impl StatementsTransformer {
           ctx_name,
         }) => match typed {
           ConstTyped::Regular => {
-            stmts.push(create_var_decl_stmt(name, Some(init), true));
+            stmts.push(create_var_decl_stmt(name, init, true));
+          }
+          ConstTyped::Memo => {
+            if let (Pat::Ident(ident), Some(init)) = (name, init) {
+              let memo = jsx_transformer.transform_memo(&ident, init);
+              stmts.push(create_var_decl_stmt(Pat::Ident(ident), None, false));
+              stmts.push(Stmt::Expr(ExprStmt {
+                span: DUMMY_SP,
+                expr: Box::new(memo),
+              }));
+            }
+          }
+          ConstTyped::Prop => {
+            stmts.extend(jsx_transformer.transform_prop(name, init));
+          }
+          ConstTyped::Slots => {
+            stmts.push(jsx_transformer.transform_slots(name));
+          }
+          ConstTyped::Context => {
+            if let Some(ctx_name) = ctx_name {
+              stmts.extend(jsx_transformer.transform_context(name, ctx_name, init));
+            }
           }
-          ConstTyped::Memo => {}
-          ConstTyped::Prop => {}
-          ConstTyped::Slots => {}
-          ConstTyped::Context => {}
         },
         Statement::FC(FCStatement {
           name,
           scope_idents,
           statements,
-        }) => {}
-        Statement::SideEffect(SideEffectStatement { name, stmt }) => {}
+        }) => {
+          let mut fc_idents = scope_idents;
+          fc_idents.inherit(&self.scope_idents.borrow());
+          let helper_component_id = fc_idents.create_ident("Component");
+          let fc_idents = Rc::new(RefCell::new(fc_idents));
+          let fc_transformer = StatementsTransformer {
+            resolver: self.resolver.clone(),
+            scope_idents: fc_idents.clone(),
+            source_map: self.source_map.clone(),
+            options: self.options.clone(),
+          };
+          let (_, fc_stmts) = fc_transformer.transform(statements);
+          self
+            .scope_idents
+            .borrow_mut()
+            .merge_helpers(&fc_idents.borrow());
+          stmts.push(Stmt::Decl(Decl::Class(ClassDecl {
+            ident: name.clone(),
+            declare: false,
+            class: create_component_class(helper_component_id, fc_stmts),
+          })));
+          // Counter.displayName = "Counter"
+          if self.options.is_dev {
+            stmts.push(create_display_name_stmt(name));
+          }
+        }
+        Statement::SideEffect(SideEffectStatement { name, stmt }) => {
+          stmts.push(Stmt::Expr(ExprStmt {
+            span: DUMMY_SP,
+            expr: Box::new(Expr::Call(CallExpr {
+              span: DUMMY_SP,
+              callee: member_expr!(DUMMY_SP, this.onMount).as_callee(),
+              args: vec![jsx_transformer.transform_side_effect(name, stmt).as_arg()],
+              type_args: None,
+            })),
+          }))
+        }
         Statement::Template(t) => match t {
           TemplateStatement::Element(el) => {
             nodes.push(jsx_transformer.transform_element(el));
@@ -227,10 +479,18 @@ impl StatementsTransformer {
             nodes.push(jsx_transformer.transform_fragment(frag));
           }
           TemplateStatement::If(if_stmt) => {
-            stmts.push(jsx_transformer.transform_condition(if_stmt));
+            nodes.push(jsx_transformer.transform_condition(if_stmt));
           }
         },
-        Statement::Style(StyleStatement { css }) => {}
+        Statement::Fallback(FallbackStatement {
+          status, template, ..
+        }) => stmts.push(jsx_transformer.transform_fallback(status, template)),
+        Statement::Style(StyleStatement { mut css }) => {
+          if !css.is_empty() && !extract_static_css(&self.resolver, &self.options, &css) {
+            styles.push(jsx_transformer.transform_style(&mut css));
+            append_css_template(&self.resolver, &css);
+          }
+        }
         Statement::Export(ExportStatement { expr }) => export_default = Some(expr),
         Statement::Stmt(stmt) => match stmt {
           Stmt::Decl(Decl::Fn(FnDecl {
@@ -296,11 +556,53 @@ impl StatementsTransformer {
       }
     }
 
+    // this.snapshot(() => ({ count })), the `let` states are saved before hot refresh
+    if !hot_states.is_empty() {
+      let snapshot = Expr::Object(ObjectLit {
+        span: DUMMY_SP,
+        props: hot_states
+          .into_iter()
+          .map(|ident| PropOrSpread::Prop(Box::new(Prop::Shorthand(ident))))
+          .collect(),
+      });
+      stmts.push(create_this_call(
+        "snapshot",
+        vec![expr_to_arrow(snapshot).as_arg()],
+      ));
+    }
+
     // const nodes = []
     // this.register(nodes)
+    // or `const nodes = (cursor) => []` and `this.hydrate(nodes)` to adopt the server-rendered
+    // DOM, the runtime calls the function with the cursor over the nodes of the component
+    let nodes: Vec<Expr> = styles.into_iter().chain(nodes).collect();
     if nodes.len() > 0 {
       let mut scope_idents = RefCell::borrow_mut(&self.scope_idents);
       let nodes_ident = scope_idents.create_ident("nodes");
+      let nodes = Expr::Array(ArrayLit {
+        span: DUMMY_SP,
+        elems: nodes
+          .into_iter()
+          .map(|node| {
+            Some(ExprOrSpread {
+              spread: None,
+              expr: Box::new(node),
+            })
+          })
+          .collect(),
+      });
+      let nodes = match jsx_transformer.cursor {
+        Some(cursor) => Expr::Arrow(ArrowExpr {
+          span: DUMMY_SP,
+          params: vec![Pat::Ident(cursor)],
+          body: BlockStmtOrExpr::Expr(Box::new(nodes)),
+          is_async: false,
+          is_generator: false,
+          type_params: None,
+          return_type: None,
+        }),
+        None => nodes,
+      };
       stmts.push(Stmt::Decl(Decl::Var(VarDecl {
         span: DUMMY_SP,
         kind: VarDeclKind::Const,
@@ -308,18 +610,7 @@ impl StatementsTransformer {
         decls: vec![VarDeclarator {
           span: DUMMY_SP,
           name: Pat::Ident(nodes_ident.clone()),
-          init: Some(Box::new(Expr::Array(ArrayLit {
-            span: DUMMY_SP,
-            elems: nodes
-              .into_iter()
-              .map(|node| {
-                Some(ExprOrSpread {
-                  spread: None,
-                  expr: Box::new(node),
-                })
-              })
-              .collect(),
-          }))),
+          init: Some(Box::new(nodes)),
           definite: false,
         }],
       })));
@@ -330,11 +621,12 @@ impl StatementsTransformer {
           obj: ExprOrSuper::Expr(Box::new(Expr::This(ThisExpr { span: DUMMY_SP }))),
           prop: Box::new(Expr::Call(CallExpr {
             span: DUMMY_SP,
-            callee: ExprOrSuper::Expr(Box::new(Expr::Ident(quote_ident!("register")))),
-            args: vec![ExprOrSpread {
-              spread: None,
-              expr: Box::new(Expr::Ident(nodes_ident.clone())),
-            }],
+            callee: ExprOrSuper::Expr(Box::new(Expr::Ident(if self.options.hydrate {
+              quote_ident!("hydrate")
+            } else {
+              quote_ident!("register")
+            }))),
+            args: vec![Expr::Ident(nodes_ident.clone()).as_arg()],
             type_args: None,
           })),
           computed: false,
@@ -346,7 +638,201 @@ impl StatementsTransformer {
   }
 }
 
-fn create_var_decl_stmt(name: Pat, init: Option<Expr>, is_const: bool) -> Stmt {
+/// create `class extends Component { constructor(props) { ... } }`
+fn create_component_class(super_class: Ident, stmts: Vec<Stmt>) -> Class {
+  Class {
+    span: DUMMY_SP,
+    decorators: vec![],
+    body: vec![ClassMember::Constructor(Constructor {
+      span: DUMMY_SP,
+      key: PropName::Ident(quote_ident!("constructor")),
+      params: vec![ParamOrTsParamProp::Param(Param {
+        span: DUMMY_SP,
+        decorators: vec![],
+        pat: Pat::Ident(quote_ident!("props")),
+      })],
+      body: Some(BlockStmt {
+        span: DUMMY_SP,
+        stmts,
+      }),
+      accessibility: None,
+      is_optional: false,
+    })],
+    super_class: Some(Box::new(Expr::Ident(super_class))),
+    is_abstract: false,
+    type_params: None,
+    super_type_params: None,
+    implements: vec![],
+  }
+}
+
+/// create `App.displayName = "App"`
+fn create_display_name_stmt(component: Ident) -> Stmt {
+  let name = component.sym.as_ref().to_owned();
+  Stmt::Expr(ExprStmt {
+    span: DUMMY_SP,
+    expr: Box::new(Expr::Assign(AssignExpr {
+      span: DUMMY_SP,
+      op: AssignOp::Assign,
+      left: PatOrExpr::Expr(Box::new(Expr::Member(MemberExpr {
+        span: DUMMY_SP,
+        obj: ExprOrSuper::Expr(Box::new(Expr::Ident(component))),
+        prop: Box::new(Expr::Ident(quote_ident!("displayName"))),
+        computed: false,
+      }))),
+      right: Box::new(Expr::Lit(Lit::Str(quote_str!(name)))),
+    })),
+  })
+}
+
+/// collect the names of the inline components, including the nested ones
+fn collect_fc_names(statements: &[Statement], names: &mut Vec<String>) {
+  for stmt in statements {
+    if let Statement::FC(FCStatement {
+      name, statements, ..
+    }) = stmt
+    {
+      names.push(name.sym.as_ref().to_owned());
+      collect_fc_names(statements, names);
+    }
+  }
+}
+
+/// create `Refresh(App, "./App.alef", ["Counter"])` to register the component for hot refresh,
+/// the inline components are created in the constructor of the component, so they are registered
+/// by name with it and restored as `./App.alef#Counter`
+fn create_refresh_stmt(
+  helper_refresh_id: Ident,
+  component: Ident,
+  id: &str,
+  fc_names: Vec<String>,
+) -> Stmt {
+  let mut args = vec![
+    Expr::Ident(component).as_arg(),
+    Lit::Str(quote_str!(id)).as_arg(),
+  ];
+  if !fc_names.is_empty() {
+    args.push(
+      Expr::Array(ArrayLit {
+        span: DUMMY_SP,
+        elems: fc_names
+          .into_iter()
+          .map(|name| Some(Lit::Str(quote_str!(name)).as_arg()))
+          .collect(),
+      })
+      .as_arg(),
+    );
+  }
+  Stmt::Expr(ExprStmt {
+    span: DUMMY_SP,
+    expr: Box::new(Expr::Call(CallExpr {
+      span: DUMMY_SP,
+      callee: ExprOrSuper::Expr(Box::new(Expr::Ident(helper_refresh_id))),
+      args,
+      type_args: None,
+    })),
+  })
+}
+
+/// create `if (import.meta.hot) { import.meta.hot.accept((mod) => { ... }) }`, the handler
+/// passes the updated component to `Refresh.update(mod.default, "./App.alef")` which snapshots
+/// the mounted instances and replaces them with the new ones restoring the states
+fn create_hot_accept_stmt(helper_refresh_id: Ident, id: &str) -> Stmt {
+  let import_meta_hot = || {
+    Box::new(Expr::Member(MemberExpr {
+      span: DUMMY_SP,
+      obj: ExprOrSuper::Expr(Box::new(Expr::MetaProp(MetaPropExpr {
+        meta: quote_ident!("import"),
+        prop: quote_ident!("meta"),
+      }))),
+      prop: Box::new(Expr::Ident(quote_ident!("hot"))),
+      computed: false,
+    }))
+  };
+  // if (mod) { Refresh.update(mod.default, "./App.alef") }
+  let update = Stmt::If(IfStmt {
+    span: DUMMY_SP,
+    test: Box::new(Expr::Ident(quote_ident!("mod"))),
+    cons: Box::new(Stmt::Block(BlockStmt {
+      span: DUMMY_SP,
+      stmts: vec![Stmt::Expr(ExprStmt {
+        span: DUMMY_SP,
+        expr: Box::new(Expr::Call(CallExpr {
+          span: DUMMY_SP,
+          callee: ExprOrSuper::Expr(Box::new(Expr::Member(MemberExpr {
+            span: DUMMY_SP,
+            obj: ExprOrSuper::Expr(Box::new(Expr::Ident(helper_refresh_id))),
+            prop: Box::new(Expr::Ident(quote_ident!("update"))),
+            computed: false,
+          }))),
+          args: vec![
+            member_expr!(DUMMY_SP, mod.default).as_arg(),
+            Lit::Str(quote_str!(id)).as_arg(),
+          ],
+          type_args: None,
+        })),
+      })],
+    })),
+    alt: None,
+  });
+  let handler = Expr::Arrow(ArrowExpr {
+    span: DUMMY_SP,
+    params: vec![Pat::Ident(quote_ident!("mod"))],
+    body: BlockStmtOrExpr::BlockStmt(BlockStmt {
+      span: DUMMY_SP,
+      stmts: vec![update],
+    }),
+    is_async: false,
+    is_generator: false,
+    type_params: None,
+    return_type: None,
+  });
+  Stmt::If(IfStmt {
+    span: DUMMY_SP,
+    test: import_meta_hot(),
+    cons: Box::new(Stmt::Block(BlockStmt {
+      span: DUMMY_SP,
+      stmts: vec![Stmt::Expr(ExprStmt {
+        span: DUMMY_SP,
+        expr: Box::new(Expr::Call(CallExpr {
+          span: DUMMY_SP,
+          callee: ExprOrSuper::Expr(Box::new(Expr::Member(MemberExpr {
+            span: DUMMY_SP,
+            obj: ExprOrSuper::Expr(import_meta_hot()),
+            prop: Box::new(Expr::Ident(quote_ident!("accept"))),
+            computed: false,
+          }))),
+          args: vec![handler.as_arg()],
+          type_args: None,
+        })),
+      })],
+    })),
+    alt: None,
+  })
+}
+
+/// create `"count" in state ? state.count : init` to restore the `let` state after hot refresh
+fn create_restored_expr(restored_ident: Ident, ident: &Ident, init: Option<Expr>) -> Expr {
+  let name = ident.sym.as_ref();
+  Expr::Cond(CondExpr {
+    span: DUMMY_SP,
+    test: Box::new(Expr::Bin(BinExpr {
+      span: DUMMY_SP,
+      op: BinaryOp::In,
+      left: Box::new(Expr::Lit(Lit::Str(quote_str!(name)))),
+      right: Box::new(Expr::Ident(restored_ident.clone())),
+    })),
+    cons: Box::new(Expr::Member(MemberExpr {
+      span: DUMMY_SP,
+      obj: ExprOrSuper::Expr(Box::new(Expr::Ident(restored_ident))),
+      prop: Box::new(Expr::Ident(quote_ident!(name))),
+      computed: false,
+    })),
+    alt: Box::new(init.unwrap_or_else(|| Expr::Ident(quote_ident!("undefined")))),
+  })
+}
+
+pub fn create_var_decl_stmt(name: Pat, init: Option<Expr>, is_const: bool) -> Stmt {
   Stmt::Decl(Decl::Var(VarDecl {
     span: DUMMY_SP,
     kind: if is_const {
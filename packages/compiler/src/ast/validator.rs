@@ -0,0 +1,234 @@
+// Copyright 2020-2021 postUI Lab. All rights reserved. MIT license.
+
+use super::statement::*;
+use swc_common::{errors::DiagnosticId, Span, Spanned};
+use swc_ecma_ast::*;
+use swc_ecma_utils::HANDLER;
+
+/// `$t` must be followed by JSX, a conditional expression or an if statement.
+pub const INVALID_TEMPLATE: &str = "A001";
+/// `$style` must be followed by a template literal.
+pub const INVALID_STYLE: &str = "A002";
+/// (warning) Unknown `$` label, only `$`, `$t`, `$t_pending`, `$t_rejected`, `$catch`, `$style` and
+/// `$_NAME` are reserved.
+pub const UNKNOWN_LABEL: &str = "A003";
+/// `$:` must be followed by an expression, a block or an arrow function.
+pub const INVALID_SIDE_EFFECT: &str = "A004";
+/// A regular `const` or a `Memo<T>` must be initialized.
+pub const MISSING_INITIALIZER: &str = "A005";
+/// `Memo<T>` must be declared with an identifier.
+pub const INVALID_MEMO: &str = "A006";
+/// `Prop<T>` must be declared with an identifier or an object pattern.
+pub const INVALID_PROP: &str = "A007";
+/// `Context<N, T>` requires a string literal name, and a provider must be declared with an identifier.
+pub const INVALID_CONTEXT: &str = "A008";
+/// `FC<Props>` must be a capitalized arrow function or function expression.
+pub const INVALID_FC: &str = "A009";
+/// The `$style` template must be a valid stylesheet.
+pub const INVALID_CSS: &str = "A010";
+/// (warning) The `$style` rule can't match any element of the templates.
+pub const UNUSED_CSS: &str = "A011";
+/// (warning) `$t_pending` and `$t_rejected` require a state initialized by `await`.
+pub const UNUSED_FALLBACK: &str = "A012";
+/// `$catch` must be followed by a template or an arrow function returning a template, and a
+/// component can have only one `$catch`.
+pub const INVALID_CATCH: &str = "A013";
+/// (warning) The rows of a list should have a `key` attribute, checked in development mode.
+pub const MISSING_KEY: &str = "A014";
+/// JSX spread children like `{...items}` are not supported.
+pub const UNSUPPORTED_SPREAD_CHILD: &str = "A015";
+/// An element can have only one `in:` and one `out:` directive, or a single `transition:`.
+pub const DUPLICATE_TRANSITION: &str = "A016";
+
+/// Validate the statements of Alef Component, all the diagnostics will be emitted by the `HANDLER`.
+pub fn validate(statements: &[Statement]) {
+  let has_async_state = statements.iter().any(|stmt| match stmt {
+    Statement::Var(VarStatement { is_async, .. }) => *is_async,
+    _ => false,
+  });
+  let mut has_catch = false;
+  for stmt in statements {
+    match stmt {
+      Statement::Catch(CatchStatement { span, .. }) => {
+        if has_catch {
+          error(
+            *span,
+            INVALID_CATCH,
+            "duplicate error boundary",
+            "a component can have only one `$catch:`, render the different errors in the same template",
+          );
+        }
+        has_catch = true;
+      }
+      Statement::Fallback(FallbackStatement { span, .. }) if !has_async_state => warn(
+        *span,
+        UNUSED_FALLBACK,
+        "the fallback template is never rendered",
+        "`$t_pending` and `$t_rejected` require a state initialized by `await`, like `let data = await fetch(url)`",
+      ),
+      Statement::Const(ConstStatement {
+        name,
+        typed,
+        init,
+        ctx_name,
+      }) => validate_const(name, typed, init, ctx_name),
+      Statement::FC(FCStatement { statements, .. }) => validate(statements),
+      Statement::SideEffect(SideEffectStatement { name, stmt }) => {
+        if let Some(name) = name {
+          if name.is_empty() {
+            error(
+              stmt.span(),
+              INVALID_SIDE_EFFECT,
+              "a named side effect requires a name",
+              "use `$_NAME:` to name the side effect, or `$:` for an anonymous one",
+            );
+          }
+        }
+        match stmt {
+          Stmt::Decl(_) | Stmt::Return(_) | Stmt::Empty(_) | Stmt::Labeled(_) => error(
+            stmt.span(),
+            INVALID_SIDE_EFFECT,
+            "invalid side effect",
+            "`$:` must be followed by an expression, a block or an arrow function",
+          ),
+          _ => {}
+        }
+      }
+      Statement::Stmt(Stmt::Labeled(LabeledStmt { label, body, .. })) => match label.sym.as_ref() {
+        name @ "$t" | name @ "$t_pending" | name @ "$t_rejected" => error(
+          body.span(),
+          INVALID_TEMPLATE,
+          "invalid template",
+          &format!(
+            "`{}:` must be followed by JSX, a conditional expression or an if statement",
+            name
+          ),
+        ),
+        "$catch" => error(
+          body.span(),
+          INVALID_CATCH,
+          "invalid error boundary",
+          "`$catch:` must be followed by a template or an arrow function returning a template, like `$catch: (error, reset) => <p>{error.message}</p>`",
+        ),
+        "$style" => error(
+          body.span(),
+          INVALID_STYLE,
+          "invalid style",
+          "`$style:` must be followed by a template literal, like $style: `p { color: red }`",
+        ),
+        name if name.starts_with('$') => warn(
+          label.span,
+          UNKNOWN_LABEL,
+          &format!("unknown label `{}`", name),
+          "only `$`, `$t`, `$t_pending`, `$t_rejected`, `$catch`, `$style` and `$_NAME` are reserved labels of Alef Component",
+        ),
+        _ => {}
+      },
+      _ => {}
+    }
+  }
+}
+
+fn validate_const(name: &Pat, typed: &ConstTyped, init: &Option<Expr>, ctx_name: &Option<String>) {
+  match typed {
+    ConstTyped::Regular => {
+      if is_typed_fc(name) {
+        error(
+          name.span(),
+          INVALID_FC,
+          "invalid inline component",
+          "`FC<Props>` must be a capitalized arrow function or function expression",
+        );
+      } else if init.is_none() {
+        error(
+          name.span(),
+          MISSING_INITIALIZER,
+          "missing initializer in const declaration",
+          "add an initializer, or use `let` to declare a state",
+        );
+      }
+    }
+    ConstTyped::Memo => {
+      if let Pat::Ident(_) = name {
+        if init.is_none() {
+          error(
+            name.span(),
+            MISSING_INITIALIZER,
+            "missing initializer in memo declaration",
+            "a memo must be initialized with an expression, like `const double: Memo<number> = n * 2`",
+          );
+        }
+      } else {
+        error(
+          name.span(),
+          INVALID_MEMO,
+          "invalid memo declaration",
+          "`Memo<T>` must be declared with an identifier",
+        );
+      }
+    }
+    ConstTyped::Prop | ConstTyped::Slots => match name {
+      Pat::Ident(_) | Pat::Object(_) => {}
+      _ => error(
+        name.span(),
+        INVALID_PROP,
+        "invalid prop declaration",
+        "`Prop<T>` must be declared with an identifier or an object pattern",
+      ),
+    },
+    ConstTyped::Context => {
+      if ctx_name.is_none() {
+        error(
+          name.span(),
+          INVALID_CONTEXT,
+          "missing context name",
+          "the first type parameter must be a string literal, like `Context<\"theme\", string>`",
+        );
+      } else if init.is_some() && !matches!(name, Pat::Ident(_)) {
+        error(
+          name.span(),
+          INVALID_CONTEXT,
+          "invalid context provider",
+          "a context provider must be declared with an identifier",
+        );
+      }
+    }
+  }
+}
+
+fn is_typed_fc(pat: &Pat) -> bool {
+  if let Pat::Ident(Ident {
+    type_ann: Some(TsTypeAnn { type_ann, .. }),
+    ..
+  }) = pat
+  {
+    if let TsType::TsTypeRef(TsTypeRef {
+      type_name: TsEntityName::Ident(Ident { sym, .. }),
+      ..
+    }) = type_ann.as_ref()
+    {
+      return sym.eq("FC");
+    }
+  }
+  false
+}
+
+fn error(span: Span, code: &str, message: &str, help: &str) {
+  HANDLER.with(|handler| {
+    handler
+      .struct_span_err(span, message)
+      .code(DiagnosticId::Error(code.into()))
+      .help(help)
+      .emit()
+  });
+}
+
+fn warn(span: Span, code: &str, message: &str, help: &str) {
+  HANDLER.with(|handler| {
+    handler
+      .struct_span_warn(span, message)
+      .code(DiagnosticId::Lint(code.into()))
+      .help(help)
+      .emit()
+  });
+}
@@ -78,17 +78,17 @@ impl ASTWalker {
                         if name.sym.chars().next().unwrap().is_ascii_uppercase() {
                           if let Some(init) = &decl.init {
                             match init.as_ref() {
-                              Expr::Arrow(ArrowExpr { body, .. }) => match body {
+                              Expr::Arrow(ArrowExpr { params, body, .. }) => match body {
                                 BlockStmtOrExpr::BlockStmt(block_stmt) => {
                                   let mut fc_walker = Self::new();
-                                  let mut fc_stmts: Vec<Statement> = vec![];
+                                  let mut fc_stmts = fc_walker.transform_fc_params(params);
                                   for stmt in &block_stmt.stmts {
                                     fc_stmts = [fc_stmts, fc_walker.transform_stmt(stmt)].concat()
                                   }
                                   for dep in fc_walker.dep_graph {
                                     self.dep_graph.push(dep)
                                   }
-                                  self.scope_idents.mark(&decl.name);
+                                  self.scope_idents.mark_component(&decl.name);
                                   stmts.push(Statement::FC(FCStatement {
                                     name: name.clone(),
                                     scope_idents: fc_walker.scope_idents,
@@ -98,15 +98,18 @@ impl ASTWalker {
                                 }
                                 BlockStmtOrExpr::Expr(expr) => {
                                   let mut fc_walker = Self::new();
-                                  let statements =
-                                    fc_walker.transform_stmt(&Stmt::Expr(ExprStmt {
+                                  let statements = [
+                                    fc_walker.transform_fc_params(params),
+                                    fc_walker.transform_stmt(&Stmt::Return(ReturnStmt {
                                       span: DUMMY_SP,
-                                      expr: expr.clone(),
-                                    }));
+                                      arg: Some(expr.clone()),
+                                    })),
+                                  ]
+                                  .concat();
                                   for dep in fc_walker.dep_graph {
                                     self.dep_graph.push(dep)
                                   }
-                                  self.scope_idents.mark(&decl.name);
+                                  self.scope_idents.mark_component(&decl.name);
                                   stmts.push(Statement::FC(FCStatement {
                                     name: name.clone(),
                                     scope_idents: fc_walker.scope_idents,
@@ -118,21 +121,24 @@ impl ASTWalker {
                               Expr::Fn(FnExpr {
                                 function:
                                   Function {
+                                    params,
                                     body: Some(body),
                                     is_generator: false,
                                     ..
                                   },
                                 ..
                               }) => {
+                                let params: Vec<Pat> =
+                                  params.iter().map(|param| param.pat.clone()).collect();
                                 let mut fc_walker = Self::new();
-                                let mut fc_stmts: Vec<Statement> = vec![];
+                                let mut fc_stmts = fc_walker.transform_fc_params(&params);
                                 for stmt in &body.stmts {
                                   fc_stmts = [fc_stmts, fc_walker.transform_stmt(stmt)].concat()
                                 }
                                 for dep in fc_walker.dep_graph {
                                   self.dep_graph.push(dep)
                                 }
-                                self.scope_idents.mark(&decl.name);
+                                self.scope_idents.mark_component(&decl.name);
                                 stmts.push(Statement::FC(FCStatement {
                                   name: name.clone(),
                                   scope_idents: fc_walker.scope_idents,
@@ -161,7 +167,7 @@ impl ASTWalker {
             stmts.push(Statement::Const(ConstStatement {
               typed,
               name: decl.name.clone(),
-              init: decl.init.clone().unwrap().as_ref().clone(),
+              init: decl.init.as_ref().map(|init| init.as_ref().clone()),
               ctx_name,
             }))
           }
@@ -222,80 +228,50 @@ impl ASTWalker {
         stmts.push(Statement::Stmt(stmt.clone()));
       }
       Stmt::Labeled(labeled) => match labeled.label.as_ref() {
-        "$" => stmts.push(Statement::SideEffect(SideEffectStatement {
-          name: None,
-          stmt: labeled.body.as_ref().clone(),
-        })),
-        "$t" => match labeled.body.as_ref() {
-          Stmt::Expr(ExprStmt { expr, .. }) => match expr.as_ref() {
-            // match `$t: <p />`
-            Expr::JSXElement(el) => stmts.push(Statement::Template(TemplateStatement::Element(
-              el.as_ref().clone(),
-            ))),
-            // match `$t: <><p /></>`
-            Expr::JSXFragment(fragment) => stmts.push(Statement::Template(
-              TemplateStatement::Fragment(fragment.clone()),
-            )),
-            // match `$t: true ? <p /> : <p />`
-            Expr::Cond(CondExpr {
-              test, cons, alt, ..
-            }) => stmts.push(Statement::Template(TemplateStatement::If(IfStmt {
-              span: DUMMY_SP,
-              test: test.clone(),
-              cons: Box::new(Stmt::Expr(ExprStmt {
-                span: DUMMY_SP,
-                expr: cons.clone(),
-              })),
-              alt: Some(Box::new(Stmt::Expr(ExprStmt {
-                span: DUMMY_SP,
-                expr: alt.clone(),
-              }))),
-            }))),
-            Expr::Bin(BinExpr {
-              op, left, right, ..
-            }) => match op {
-              // match `$t: true && <p />` OR `$t: true && 1 && <p />`
-              BinaryOp::LogicalAnd => {
-                stmts.push(Statement::Template(TemplateStatement::If(IfStmt {
-                  span: DUMMY_SP,
-                  test: left.clone(),
-                  cons: Box::new(Stmt::Expr(ExprStmt {
-                    span: DUMMY_SP,
-                    expr: right.clone(),
-                  })),
-                  alt: None,
-                })))
-              }
-              // match `$t: false || <p />` OR `$t: false || 0 || <p />`
-              BinaryOp::LogicalOr => {
-                stmts.push(Statement::Template(TemplateStatement::If(IfStmt {
-                  span: DUMMY_SP,
-                  test: Box::new(Expr::Unary(UnaryExpr {
-                    span: DUMMY_SP,
-                    op: UnaryOp::Bang,
-                    arg: left.clone(),
-                  })),
-                  cons: Box::new(Stmt::Expr(ExprStmt {
-                    span: DUMMY_SP,
-                    expr: right.clone(),
-                  })),
-                  alt: None,
-                })))
-              }
-              _ => stmts.push(Statement::Stmt(stmt.clone())),
-            },
-            _ => stmts.push(Statement::Stmt(stmt.clone())),
-          },
-          // match `$t: if (...) <p /> else if (...) <p /> else <p />`
-          Stmt::If(IfStmt {
-            test, cons, alt, ..
-          }) => stmts.push(Statement::Template(TemplateStatement::If(IfStmt {
-            span: DUMMY_SP,
-            test: test.clone(),
-            cons: cons.clone(),
-            alt: alt.clone(),
-          }))),
-          _ => stmts.push(Statement::Stmt(stmt.clone())),
+        "$" => match as_memo_assign(labeled.body.as_ref()) {
+          // match `$: double = n * 2` that the `double` is not declared
+          Some((ident, expr)) if !self.scope_idents.scopes.contains(ident.sym.as_ref()) => {
+            let name = Pat::Ident(ident.clone());
+            self.scope_idents.mark_memo(&name);
+            stmts.push(Statement::Const(ConstStatement {
+              typed: ConstTyped::Memo,
+              name,
+              init: Some(expr.clone()),
+              ctx_name: None,
+            }))
+          }
+          _ => stmts.push(Statement::SideEffect(SideEffectStatement {
+            name: None,
+            stmt: labeled.body.as_ref().clone(),
+          })),
+        },
+        "$t" => match transform_template(labeled.body.as_ref()) {
+          Some(template) => stmts.push(Statement::Template(template)),
+          None => stmts.push(Statement::Stmt(stmt.clone())),
+        },
+        // match `$t_pending: <p>Loading...</p>` and `$t_rejected: <p>Failed</p>`
+        label @ "$t_pending" | label @ "$t_rejected" => {
+          match transform_template(labeled.body.as_ref()) {
+            Some(template) => stmts.push(Statement::Fallback(FallbackStatement {
+              span: labeled.span,
+              status: if label.eq("$t_pending") {
+                AsyncStatus::Pending
+              } else {
+                AsyncStatus::Rejected
+              },
+              template,
+            })),
+            None => stmts.push(Statement::Stmt(stmt.clone())),
+          }
+        }
+        // match `$catch: (error, reset) => <p>{error.message}</p>` and `$catch: <p>Oops</p>`
+        "$catch" => match transform_catch(labeled.body.as_ref()) {
+          Some((params, template)) => stmts.push(Statement::Catch(CatchStatement {
+            span: labeled.span,
+            params,
+            template,
+          })),
+          None => stmts.push(Statement::Stmt(stmt.clone())),
         },
         "$style" => match labeled.body.as_ref() {
           Stmt::Expr(ExprStmt { expr, .. }) => match expr.as_ref() {
@@ -335,6 +311,33 @@ impl ASTWalker {
     stmts
   }
 
+  /// transform the props param of FC, `({ title }: Props) => ...` reads `title` as a prop
+  fn transform_fc_params(&mut self, params: &[Pat]) -> Vec<Statement> {
+    match params.first() {
+      Some(Pat::Object(pat)) => {
+        let name = Pat::Object(pat.clone());
+        self.scope_idents.mark_prop(&name);
+        vec![Statement::Const(ConstStatement {
+          typed: ConstTyped::Prop,
+          name,
+          init: None,
+          ctx_name: None,
+        })]
+      }
+      Some(Pat::Ident(Ident { sym, .. })) if !sym.eq("props") => {
+        let name = Pat::Ident(Ident::new(sym.clone(), DUMMY_SP));
+        self.scope_idents.mark(&name);
+        vec![Statement::Const(ConstStatement {
+          typed: ConstTyped::Regular,
+          name,
+          init: Some(Expr::Ident(Ident::new("props".into(), DUMMY_SP))),
+          ctx_name: None,
+        })]
+      }
+      _ => vec![],
+    }
+  }
+
   pub fn walk(&mut self, module_items: Vec<ModuleItem>) -> Vec<Statement> {
     let mut stmts: Vec<Statement> = vec![];
 
@@ -349,7 +352,11 @@ impl ASTWalker {
               if let ImportSpecifier::Default(ImportDefaultSpecifier { local, .. })
               | ImportSpecifier::Named(ImportNamedSpecifier { local, .. }) = specifier
               {
-                self.scope_idents.mark(&Pat::Ident(local))
+                if src.ends_with(".alef") {
+                  self.scope_idents.mark_component(&Pat::Ident(local))
+                } else {
+                  self.scope_idents.mark(&Pat::Ident(local))
+                }
               }
             }
             self.dep_graph.push(DependencyDescriptor {
@@ -376,3 +383,128 @@ impl ASTWalker {
     stmts
   }
 }
+
+/// transform the body of `$t:` to template, returns `None` if it is not a template
+fn transform_template(body: &Stmt) -> Option<TemplateStatement> {
+  match body {
+    Stmt::Expr(ExprStmt { expr, .. }) => match unwrap_paren(expr.as_ref()) {
+      // match `$t: <p />`
+      Expr::JSXElement(el) => Some(TemplateStatement::Element(el.as_ref().clone())),
+      // match `$t: <><p /></>`
+      Expr::JSXFragment(fragment) => Some(TemplateStatement::Fragment(fragment.clone())),
+      // match `$t: true ? <p /> : <p />`
+      Expr::Cond(CondExpr {
+        test, cons, alt, ..
+      }) => Some(TemplateStatement::If(IfStmt {
+        span: DUMMY_SP,
+        test: test.clone(),
+        cons: Box::new(Stmt::Expr(ExprStmt {
+          span: DUMMY_SP,
+          expr: cons.clone(),
+        })),
+        alt: Some(Box::new(Stmt::Expr(ExprStmt {
+          span: DUMMY_SP,
+          expr: alt.clone(),
+        }))),
+      })),
+      Expr::Bin(BinExpr {
+        op, left, right, ..
+      }) => match op {
+        // match `$t: true && <p />` OR `$t: true && 1 && <p />`
+        BinaryOp::LogicalAnd => Some(TemplateStatement::If(IfStmt {
+          span: DUMMY_SP,
+          test: left.clone(),
+          cons: Box::new(Stmt::Expr(ExprStmt {
+            span: DUMMY_SP,
+            expr: right.clone(),
+          })),
+          alt: None,
+        })),
+        // match `$t: false || <p />` OR `$t: false || 0 || <p />`
+        BinaryOp::LogicalOr => Some(TemplateStatement::If(IfStmt {
+          span: DUMMY_SP,
+          test: Box::new(Expr::Unary(UnaryExpr {
+            span: DUMMY_SP,
+            op: UnaryOp::Bang,
+            arg: left.clone(),
+          })),
+          cons: Box::new(Stmt::Expr(ExprStmt {
+            span: DUMMY_SP,
+            expr: right.clone(),
+          })),
+          alt: None,
+        })),
+        _ => None,
+      },
+      _ => None,
+    },
+    // match `$t: if (...) <p /> else if (...) <p /> else <p />`
+    Stmt::If(IfStmt {
+      test, cons, alt, ..
+    }) => Some(TemplateStatement::If(IfStmt {
+      span: DUMMY_SP,
+      test: test.clone(),
+      cons: cons.clone(),
+      alt: alt.clone(),
+    })),
+    _ => None,
+  }
+}
+
+/// transform the body of `$catch:` to the params and the template of the fallback,
+/// returns `None` if it is not a template or an arrow function returning a template
+fn transform_catch(body: &Stmt) -> Option<(Vec<Pat>, TemplateStatement)> {
+  if let Stmt::Expr(ExprStmt { expr, .. }) = body {
+    if let Expr::Arrow(ArrowExpr {
+      params,
+      body: BlockStmtOrExpr::Expr(expr),
+      ..
+    }) = unwrap_paren(expr.as_ref())
+    {
+      let body = Stmt::Expr(ExprStmt {
+        span: DUMMY_SP,
+        expr: expr.clone(),
+      });
+      return transform_template(&body).map(|template| (params.clone(), template));
+    }
+  }
+  transform_template(body).map(|template| (vec![], template))
+}
+
+/// unwrap `(...)` of the expression
+fn unwrap_paren(expr: &Expr) -> &Expr {
+  match expr {
+    Expr::Paren(ParenExpr { expr, .. }) => unwrap_paren(expr.as_ref()),
+    _ => expr,
+  }
+}
+
+/// match the `x = expr` statement
+fn as_memo_assign(stmt: &Stmt) -> Option<(&Ident, &Expr)> {
+  if let Stmt::Expr(ExprStmt { expr, .. }) = stmt {
+    if let Expr::Assign(AssignExpr {
+      op: AssignOp::Assign,
+      left,
+      right,
+      ..
+    }) = expr.as_ref()
+    {
+      let ident = match left {
+        PatOrExpr::Pat(pat) => match pat.as_ref() {
+          Pat::Ident(ident) => Some(ident),
+          Pat::Expr(expr) => match expr.as_ref() {
+            Expr::Ident(ident) => Some(ident),
+            _ => None,
+          },
+          _ => None,
+        },
+        PatOrExpr::Expr(expr) => match expr.as_ref() {
+          Expr::Ident(ident) => Some(ident),
+          _ => None,
+        },
+      };
+      return ident.map(|ident| (ident, right.as_ref()));
+    }
+  }
+  None
+}
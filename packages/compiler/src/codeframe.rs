@@ -0,0 +1,357 @@
+// Copyright 2020-2021 postUI Lab. All rights reserved. MIT license.
+
+use crate::error::{DiagnosticBuffer, DiagnosticLabel, DiagnosticMessage, Severity};
+use std::{collections::BTreeSet, path::Path};
+use swc_common::{FileName, SourceMap};
+
+/// The number of source lines to show around a labeled line.
+const CONTEXT_LINES: usize = 1;
+
+/// The output style of code frames.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FrameStyle {
+  /// plain text
+  Plain,
+  /// text with ANSI colours for terminals
+  Ansi,
+  /// HTML for the browser error overlay
+  Html,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Paint {
+  Error,
+  Warning,
+  Info,
+  Gutter,
+  Bold,
+}
+
+impl From<Severity> for Paint {
+  fn from(severity: Severity) -> Self {
+    match severity {
+      Severity::Error => Paint::Error,
+      Severity::Warning => Paint::Warning,
+      Severity::Note | Severity::Help => Paint::Info,
+    }
+  }
+}
+
+/// A renderer to render diagnostics as rustc/babel-style code frames.
+pub struct CodeFrame<'a> {
+  source_map: &'a SourceMap,
+  style: FrameStyle,
+}
+
+impl<'a> CodeFrame<'a> {
+  pub fn new(source_map: &'a SourceMap, style: FrameStyle) -> Self {
+    CodeFrame { source_map, style }
+  }
+
+  /// Render all the diagnostics in the buffer.
+  pub fn render(&self, diagnostics: &DiagnosticBuffer) -> String {
+    let frames: Vec<String> = diagnostics
+      .0
+      .iter()
+      .map(|d| self.render_message(d))
+      .collect();
+    let output = frames.join("\n");
+    match self.style {
+      FrameStyle::Html => format!("<pre class=\"alef-code-frame\">{}</pre>", output),
+      _ => output,
+    }
+  }
+
+  /// Render a diagnostic message with the source excerpt it points to.
+  pub fn render_message(&self, d: &DiagnosticMessage) -> String {
+    let mut lines: Vec<String> = vec![];
+    let severity = match &d.code {
+      Some(code) => format!("{}[{}]", d.severity, code),
+      None => d.severity.to_string(),
+    };
+    lines.push(format!(
+      "{}{}",
+      self.paint(&severity, d.severity.into()),
+      self.paint(&format!(": {}", d.message), Paint::Bold)
+    ));
+    if d.file.is_empty() {
+      self.push_sub_messages(&mut lines, d, "");
+      return lines.join("\n");
+    }
+
+    let file = self
+      .source_map
+      .get_source_file(&FileName::Real(Path::new(&d.file).to_path_buf()));
+    let src = match &file {
+      Some(file) => file.src.as_str(),
+      None => "",
+    };
+    let line_starts: Vec<usize> = std::iter::once(0)
+      .chain(src.match_indices('\n').map(|(i, _)| i + 1))
+      .collect();
+    let labels: Vec<DiagnosticLabel> = if d.labels.is_empty() {
+      vec![DiagnosticLabel {
+        span: d.span,
+        message: None,
+        primary: true,
+      }]
+    } else {
+      let mut labels = d.labels.clone();
+      labels.sort_by_key(|l| (!l.primary, l.span.start));
+      labels
+    };
+    let mut shown_lines: BTreeSet<usize> = BTreeSet::new();
+    if file.is_some() {
+      for label in &labels {
+        let line = line_index(&line_starts, label.span.start as usize);
+        let start = line.saturating_sub(CONTEXT_LINES);
+        let end = (line + CONTEXT_LINES).min(line_starts.len() - 1);
+        for i in start..=end {
+          shown_lines.insert(i);
+        }
+      }
+    }
+    let gutter_width = match shown_lines.iter().last() {
+      Some(line) => (line + 1).to_string().len(),
+      None => d.line.to_string().len(),
+    };
+    let pad = " ".repeat(gutter_width);
+
+    lines.push(format!(
+      "{}{} {}:{}:{}",
+      pad,
+      self.paint("-->", Paint::Gutter),
+      self.text(&d.file),
+      d.line,
+      d.col + 1
+    ));
+    if shown_lines.is_empty() {
+      self.push_sub_messages(&mut lines, d, &pad);
+      return lines.join("\n");
+    }
+
+    lines.push(format!("{} {}", pad, self.paint("|", Paint::Gutter)));
+    let mut prev_line: Option<usize> = None;
+    for line in shown_lines {
+      if let Some(prev) = prev_line {
+        if line > prev + 1 {
+          lines.push(self.paint("...", Paint::Gutter));
+        }
+      }
+      prev_line = Some(line);
+      let line_start = line_starts[line];
+      let line_end = match line_starts.get(line + 1) {
+        Some(next) => next - 1,
+        None => src.len(),
+      };
+      let text = src[line_start..line_end].trim_end_matches('\r');
+      let content_end = line_start + text.len();
+      lines.push(format!(
+        "{}{}",
+        self.paint(
+          &format!("{:>width$} |", line + 1, width = gutter_width),
+          Paint::Gutter
+        ),
+        if text.is_empty() {
+          String::new()
+        } else {
+          format!(" {}", self.text(&expand_tabs(text)))
+        }
+      ));
+      for label in &labels {
+        let start = (label.span.start as usize).min(src.len());
+        if line_index(&line_starts, start) != line {
+          continue;
+        }
+        // the label may point at the line break, or inside of a char when the span comes from
+        // the deserialized diagnostics
+        let start = floor_char_boundary(src, start.min(content_end));
+        let end = floor_char_boundary(src, (label.span.end as usize).max(start).min(content_end));
+        let offset = display_width(&src[line_start..start]);
+        let width = display_width(&src[start..end]).max(1);
+        let (mark, paint) = if label.primary {
+          ("^", Paint::from(d.severity))
+        } else {
+          ("-", Paint::Info)
+        };
+        let mut underline = mark.repeat(width);
+        if let Some(message) = &label.message {
+          underline = format!("{} {}", underline, message);
+        }
+        lines.push(format!(
+          "{} {} {}{}",
+          pad,
+          self.paint("|", Paint::Gutter),
+          " ".repeat(offset),
+          self.paint(&underline, paint)
+        ));
+      }
+    }
+    lines.push(format!("{} {}", pad, self.paint("|", Paint::Gutter)));
+    self.push_sub_messages(&mut lines, d, &pad);
+    lines.join("\n")
+  }
+
+  fn push_sub_messages(&self, lines: &mut Vec<String>, d: &DiagnosticMessage, pad: &str) {
+    for (kind, messages) in &[("note", &d.notes), ("help", &d.help)] {
+      for message in messages.iter() {
+        lines.push(format!(
+          "{} {} {}: {}",
+          pad,
+          self.paint("=", Paint::Gutter),
+          self.paint(kind, Paint::Bold),
+          self.text(message)
+        ));
+      }
+    }
+  }
+
+  fn paint(&self, text: &str, paint: Paint) -> String {
+    match self.style {
+      FrameStyle::Plain => text.into(),
+      FrameStyle::Ansi => {
+        let color = match paint {
+          Paint::Error => "\x1b[1;31m",
+          Paint::Warning => "\x1b[1;33m",
+          Paint::Info => "\x1b[1;36m",
+          Paint::Gutter => "\x1b[1;34m",
+          Paint::Bold => "\x1b[1m",
+        };
+        format!("{}{}\x1b[0m", color, text)
+      }
+      FrameStyle::Html => {
+        let class = match paint {
+          Paint::Error => "error",
+          Paint::Warning => "warning",
+          Paint::Info => "info",
+          Paint::Gutter => "gutter",
+          Paint::Bold => "bold",
+        };
+        format!(
+          "<span class=\"alef-{}\">{}</span>",
+          class,
+          escape_html(text)
+        )
+      }
+    }
+  }
+
+  fn text(&self, text: &str) -> String {
+    match self.style {
+      FrameStyle::Html => escape_html(text),
+      _ => text.into(),
+    }
+  }
+}
+
+/// Render diagnostics of the source as code frames.
+pub fn render_code_frames(
+  specifier: &str,
+  source: &str,
+  diagnostics: &DiagnosticBuffer,
+  style: FrameStyle,
+) -> String {
+  let source_map = SourceMap::default();
+  source_map.new_source_file(
+    FileName::Real(Path::new(specifier).to_path_buf()),
+    source.into(),
+  );
+  CodeFrame::new(&source_map, style).render(diagnostics)
+}
+
+/// Returns the (0-based) line index of the byte offset.
+fn line_index(line_starts: &[usize], offset: usize) -> usize {
+  match line_starts.binary_search(&offset) {
+    Ok(line) => line,
+    Err(line) => line - 1,
+  }
+}
+
+/// Returns the nearest char boundary at or before the byte offset.
+fn floor_char_boundary(s: &str, mut offset: usize) -> usize {
+  while !s.is_char_boundary(offset) {
+    offset -= 1;
+  }
+  offset
+}
+
+fn expand_tabs(s: &str) -> String {
+  s.replace('\t', "    ")
+}
+
+fn display_width(s: &str) -> usize {
+  s.chars().map(|c| if c == '\t' { 4 } else { 1 }).sum()
+}
+
+fn escape_html(s: &str) -> String {
+  s.replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::error::DiagnosticSpan;
+
+  const SOURCE: &str = "let n = 0\n\n$t: <p>{n</p>\n";
+
+  fn new_message(start: u32, end: u32) -> DiagnosticMessage {
+    DiagnosticMessage {
+      message: "Unexpected token".into(),
+      severity: Severity::Error,
+      code: None,
+      file: "App.alef".into(),
+      line: 3,
+      col: 7,
+      span: DiagnosticSpan { start, end },
+      labels: vec![],
+      notes: vec!["JSX expressions must be closed".into()],
+      help: vec![],
+    }
+  }
+
+  #[test]
+  fn test_render_plain() {
+    let diagnostics = DiagnosticBuffer(vec![new_message(18, 20)]);
+    let output = render_code_frames("App.alef", SOURCE, &diagnostics, FrameStyle::Plain);
+    assert_eq!(
+      output,
+      [
+        "error: Unexpected token",
+        " --> App.alef:3:8",
+        "  |",
+        "2 |",
+        "3 | $t: <p>{n</p>",
+        "  |        ^^",
+        "4 |",
+        "  |",
+        "  = note: JSX expressions must be closed",
+      ]
+      .join("\n")
+    );
+  }
+
+  #[test]
+  fn test_render_html() {
+    let diagnostics = DiagnosticBuffer(vec![new_message(18, 20)]);
+    let output = render_code_frames("App.alef", SOURCE, &diagnostics, FrameStyle::Html);
+    assert!(output.starts_with("<pre class=\"alef-code-frame\">"));
+    assert!(output.contains("$t: &lt;p&gt;{n&lt;/p&gt;"));
+    assert!(output.contains("<span class=\"alef-error\">^^</span>"));
+  }
+
+  #[test]
+  fn test_render_crlf() {
+    let source = "let n = 0\r\nlet s = '\u{e9}'\r\n";
+    // the label points at the `\n` of the first line
+    let diagnostics = DiagnosticBuffer(vec![new_message(10, 11)]);
+    let output = render_code_frames("App.alef", source, &diagnostics, FrameStyle::Plain);
+    assert!(output.contains("1 | let n = 0\n  |          ^\n"));
+    // the label starts inside of the `\u{e9}`
+    let diagnostics = DiagnosticBuffer(vec![new_message(21, 23)]);
+    let output = render_code_frames("App.alef", source, &diagnostics, FrameStyle::Plain);
+    assert!(output.contains("2 | let s = '\u{e9}'\n  |          ^^\n"));
+  }
+}
@@ -2,25 +2,67 @@
 
 use super::{
   ast::alef_transform,
-  error::{DiagnosticBuffer, ErrorBuffer},
-  resolve::Resolver,
+  codeframe::{CodeFrame, FrameStyle},
+  error::{DiagnosticBuffer, ErrorBuffer, Severity},
+  resolve::{Resolver, Target},
 };
 use std::{cell::RefCell, path::Path, rc::Rc};
 use swc_common::{
   chain,
   comments::SingleThreadedComments,
   errors::{Handler, HandlerFlags},
-  FileName, Globals, SourceMap,
+  pass::Optional,
+  BytePos, FileName, Globals, LineCol, Mark, SourceMap,
 };
+use swc_ecma_utils::HANDLER;
 use swc_ecmascript::{
   ast::{Module, Program},
   codegen::{text_writer::JsWriter, Node},
   parser::lexer::Lexer,
   parser::{JscTarget, StringInput, Syntax, TsConfig},
-  transforms::{fixer, helpers, typescript},
+  transforms::{compat, fixer, helpers, hygiene, resolver::resolver_with_mark, typescript},
   visit::{Fold, FoldWith},
 };
 
+/// Options for transpiling Alef Component.
+#[derive(Clone, Debug)]
+pub struct EmitOptions {
+  /// the ES version of the output, the newer syntax is transformed by the compat passes, `Es2019`
+  /// lowers the es2020 syntax only as its optional catch binding is kept until `Es2017`
+  pub target: Target,
+  /// codes of the warnings to ignore, like `["A003"]`
+  pub ignore_warnings: Vec<String>,
+  /// development mode, unused style rules are kept as comments instead of being stripped, the
+  /// elements are located in the source, the props are asserted at runtime and the list rows
+  /// without `key` are warned
+  pub is_dev: bool,
+  /// extract the static styles into a separate stylesheet instead of injecting them at runtime
+  pub extract_css: bool,
+  /// server-side rendering, emit a render function that returns the HTML string instead of
+  /// the component class
+  pub ssr: bool,
+  /// hydrate the server-rendered DOM instead of creating the DOM nodes on the client, the nodes
+  /// are adopted by walking the hydration markers of the SSR output
+  pub hydrate: bool,
+  /// hot refresh, register the components for module replacement and keep the `let` states
+  /// across it, ignored in SSR mode
+  pub hot_refresh: bool,
+}
+
+impl Default for EmitOptions {
+  fn default() -> Self {
+    EmitOptions {
+      target: Target::Es2020,
+      ignore_warnings: vec![],
+      is_dev: false,
+      extract_css: false,
+      ssr: false,
+      hydrate: false,
+      hot_refresh: false,
+    }
+  }
+}
+
 #[derive(Clone)]
 pub struct Compiler {
   pub specifier: String,
@@ -30,8 +72,8 @@ pub struct Compiler {
 }
 
 impl Compiler {
-  /// Parse Alef Component to AST.
-  pub fn parse(specifier: &str, source: &str) -> Result<Self, anyhow::Error> {
+  /// Parse Alef Component to AST, returns all the diagnostics of the parser if it fails.
+  pub fn parse(specifier: &str, source: &str) -> Result<Self, DiagnosticBuffer> {
     let source_map = SourceMap::default();
     let source_file = source_map.new_source_file(
       FileName::Real(Path::new(specifier).to_path_buf()),
@@ -47,6 +89,7 @@ impl Compiler {
     });
     let input = StringInput::from(&*source_file);
     let comments = SingleThreadedComments::default();
+    // the source is parsed with the latest syntax, `EmitOptions.target` applies to the output
     let lexer = Lexer::new(syntax, JscTarget::Es2020, input, Some(&comments));
     let mut parser = swc_ecmascript::parser::Parser::new_from(lexer);
     let handler = Handler::with_emitter_and_flags(
@@ -57,14 +100,24 @@ impl Compiler {
         ..HandlerFlags::default()
       },
     );
-    let module = parser
-      .parse_module()
-      .map_err(move |err| {
-        let mut diagnostic = err.into_diagnostic(&handler);
-        diagnostic.emit();
-        DiagnosticBuffer::from_error_buffer(error_buffer, |span| sm.lookup_char_pos(span.lo))
-      })
-      .unwrap();
+    let module = match parser.parse_module() {
+      Ok(module) => Some(module),
+      Err(err) => {
+        err.into_diagnostic(&handler).emit();
+        None
+      }
+    };
+    for err in parser.take_errors() {
+      err.into_diagnostic(&handler).emit();
+    }
+    let module = match module {
+      Some(module) if error_buffer.is_empty() => module,
+      _ => {
+        return Err(DiagnosticBuffer::from_error_buffer(error_buffer, |span| {
+          sm.lookup_char_pos(span.lo)
+        }))
+      }
+    };
 
     Ok(Compiler {
       specifier: specifier.into(),
@@ -74,29 +127,79 @@ impl Compiler {
     })
   }
 
-  /// Transform Alef Component into Javascript.
+  /// Render diagnostics of the component as code frames.
+  pub fn render_diagnostics(&self, diagnostics: &DiagnosticBuffer, style: FrameStyle) -> String {
+    CodeFrame::new(&self.source_map, style).render(diagnostics)
+  }
+
+  /// Transform Alef Component into Javascript, returns all the errors of the transform if it fails,
+  /// the warnings will be stored in the resolver.
   pub fn transpile(
     self,
     resolver: Rc<RefCell<Resolver>>,
+    options: &EmitOptions,
   ) -> Result<(String, Option<String>), anyhow::Error> {
-    let mut passes = chain!(
-      alef_transform(resolver.clone()),
-      typescript::strip(),
-      fixer(Some(&self.comments)),
+    let error_buffer = ErrorBuffer::new();
+    let handler = Handler::with_emitter_and_flags(
+      Box::new(error_buffer.clone()),
+      HandlerFlags {
+        can_emit_warnings: true,
+        dont_buffer_diagnostics: true,
+        ..HandlerFlags::default()
+      },
     );
-
-    self.apply_transform(&mut passes)
+    let output = swc_common::GLOBALS.set(&Globals::new(), || {
+      let target = options.target;
+      let top_level_mark = Mark::fresh(Mark::root());
+      let passes = chain!(
+        alef_transform(resolver.clone(), self.source_map.clone(), options),
+        typescript::strip(),
+        Optional::new(resolver_with_mark(top_level_mark), target < Target::Es2020),
+        Optional::new(compat::es2020(), target < Target::Es2020),
+        // no es2019 pass, the optional catch binding of es2019 is lowered by the es2018 pass
+        Optional::new(compat::es2018(), target < Target::Es2018),
+        Optional::new(compat::es2017(), target < Target::Es2017),
+        Optional::new(compat::es2016(), target < Target::Es2016),
+        Optional::new(
+          compat::es2015(top_level_mark, Default::default()),
+          target < Target::Es2015
+        ),
+        Optional::new(compat::es3(true), target < Target::Es5),
+        Optional::new(helpers::inject_helpers(), target < Target::Es2020),
+        Optional::new(hygiene(), target < Target::Es2020),
+        fixer(Some(&self.comments)),
+      );
+      HANDLER.set(&handler, || self.fold_and_emit(passes))
+    })?;
+    let mut diagnostics = DiagnosticBuffer::from_error_buffer(error_buffer, |span| {
+      self.source_map.lookup_char_pos(span.lo)
+    });
+    diagnostics.0.retain(|d| match &d.code {
+      Some(code) if d.severity != Severity::Error => !options.ignore_warnings.contains(code),
+      _ => true,
+    });
+    if diagnostics.has_errors() {
+      return Err(diagnostics.into());
+    }
+    let mut resolver = resolver.borrow_mut();
+    if let Some(css) = resolver.extracted_css.as_mut() {
+      css.map = Some(self.build_source_map(&mut css.mappings));
+    }
+    resolver.diagnostics = diagnostics;
+    Ok(output)
   }
 
   /// Apply transform with given fold.
-  pub fn apply_transform<T: Fold>(
-    &self,
-    mut tr: T,
-  ) -> Result<(String, Option<String>), anyhow::Error> {
+  pub fn apply_transform<T: Fold>(&self, tr: T) -> Result<(String, Option<String>), anyhow::Error> {
+    swc_common::GLOBALS.set(&Globals::new(), || self.fold_and_emit(tr))
+  }
+
+  /// Fold the module and emit the code, the `swc_common::GLOBALS` must be set by the caller as the
+  /// marks of the fold are created with it.
+  fn fold_and_emit<T: Fold>(&self, mut tr: T) -> Result<(String, Option<String>), anyhow::Error> {
     let program = Program::Module(self.module.clone());
-    let program = swc_common::GLOBALS.set(&Globals::new(), || {
-      helpers::HELPERS.set(&helpers::Helpers::new(false), || program.fold_with(&mut tr))
-    });
+    let program =
+      helpers::HELPERS.set(&helpers::Helpers::new(false), || program.fold_with(&mut tr));
     let mut buf = Vec::new();
     let mut src_map_buf = Vec::new();
     let src_map = Some(&mut src_map_buf);
@@ -118,12 +221,16 @@ impl Compiler {
       program.emit_with(&mut emitter).unwrap();
     }
     let src = String::from_utf8(buf).unwrap();
+    Ok((src, Some(self.build_source_map(&mut src_map_buf))))
+  }
+
+  fn build_source_map(&self, mappings: &mut Vec<(BytePos, LineCol)>) -> String {
     let mut buf = Vec::new();
     self
       .source_map
-      .build_source_map_from(&mut src_map_buf, None)
+      .build_source_map_from(mappings, None)
       .to_writer(&mut buf)
       .unwrap();
-    Ok((src, Some(String::from_utf8(buf).unwrap())))
+    String::from_utf8(buf).unwrap()
   }
 }
@@ -1,21 +1,117 @@
 // Copyright 2020-2021 postUI Lab. All rights reserved. MIT license.
 
-use std::{fmt, sync::Arc, sync::RwLock};
+use serde::{Deserialize, Serialize};
+use std::{error::Error, fmt, sync::Arc, sync::RwLock};
 use swc_common::{
-  errors::{Diagnostic, DiagnosticBuilder, Emitter},
-  FileName, Loc, Span,
+  errors::{Diagnostic, DiagnosticBuilder, DiagnosticId, Emitter, Level},
+  BytePos, FileName, Loc, Span,
 };
 
+/// The severity of a diagnostic message.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Severity {
+  Error,
+  Warning,
+  Note,
+  Help,
+}
+
+impl From<Level> for Severity {
+  fn from(level: Level) -> Self {
+    match level {
+      Level::Warning => Severity::Warning,
+      Level::Note => Severity::Note,
+      Level::Help => Severity::Help,
+      _ => Severity::Error,
+    }
+  }
+}
+
+impl fmt::Display for Severity {
+  fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fmt.pad(match self {
+      Severity::Error => "error",
+      Severity::Warning => "warning",
+      Severity::Note => "note",
+      Severity::Help => "help",
+    })
+  }
+}
+
+/// The byte range of a diagnostic, relative to the start of its source file.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct DiagnosticSpan {
+  pub start: u32,
+  pub end: u32,
+}
+
+impl DiagnosticSpan {
+  fn new(span: Span, start_pos: BytePos) -> Self {
+    DiagnosticSpan {
+      start: span.lo.0.saturating_sub(start_pos.0),
+      end: span.hi.0.saturating_sub(start_pos.0),
+    }
+  }
+}
+
+/// A labeled span of a diagnostic, the primary label points to the cause of the diagnostic.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct DiagnosticLabel {
+  pub span: DiagnosticSpan,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub message: Option<String>,
+  pub primary: bool,
+}
+
+/// A diagnostic message with its location in the source file.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticMessage {
+  pub message: String,
+  pub severity: Severity,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub code: Option<String>,
+  pub file: String,
+  /// The (1-based) line number
+  pub line: usize,
+  /// The (0-based) column offset when displayed
+  pub col: usize,
+  pub span: DiagnosticSpan,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub labels: Vec<DiagnosticLabel>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub notes: Vec<String>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub help: Vec<String>,
+}
+
+impl fmt::Display for DiagnosticMessage {
+  fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if self.file.is_empty() {
+      return fmt.pad(&self.message);
+    }
+    write!(
+      fmt,
+      "{} at {}:{}:{}",
+      self.message, self.file, self.line, self.col
+    )
+  }
+}
+
 /// A buffer for collecting diagnostic messages from the AST parser.
-#[derive(Debug)]
-pub struct DiagnosticBuffer(Vec<String>);
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct DiagnosticBuffer(pub Vec<DiagnosticMessage>);
 
 impl fmt::Display for DiagnosticBuffer {
   fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
-    fmt.pad(&self.0.join(","))
+    let messages: Vec<String> = self.0.iter().map(|d| d.to_string()).collect();
+    fmt.pad(&messages.join(","))
   }
 }
 
+impl Error for DiagnosticBuffer {}
+
 impl DiagnosticBuffer {
   pub fn from_error_buffer<F>(error_buffer: ErrorBuffer, get_loc: F) -> Self
   where
@@ -25,18 +121,49 @@ impl DiagnosticBuffer {
     let diagnostics = diagnostics
       .iter()
       .map(|d| {
-        let mut message = d.message();
+        let mut message = DiagnosticMessage {
+          message: d.message(),
+          severity: d.level.into(),
+          code: match &d.code {
+            Some(DiagnosticId::Error(code)) | Some(DiagnosticId::Lint(code)) => Some(code.clone()),
+            None => None,
+          },
+          file: String::new(),
+          line: 0,
+          col: 0,
+          span: DiagnosticSpan::default(),
+          labels: vec![],
+          notes: vec![],
+          help: vec![],
+        };
 
         if let Some(span) = d.span.primary_span() {
           let loc = get_loc(span);
-          let file_name = match &loc.file.name {
-            FileName::Real(p) => p.display(),
-            _ => unreachable!(),
+          let start_pos = loc.file.start_pos;
+          message.file = match &loc.file.name {
+            FileName::Real(p) => p.display().to_string(),
+            name => name.to_string(),
           };
-          message = format!(
-            "{} at {}:{}:{}",
-            message, file_name, loc.line, loc.col_display
-          );
+          message.line = loc.line;
+          message.col = loc.col_display;
+          message.span = DiagnosticSpan::new(span, start_pos);
+          message.labels = d
+            .span
+            .span_labels()
+            .into_iter()
+            .map(|label| DiagnosticLabel {
+              span: DiagnosticSpan::new(label.span, start_pos),
+              message: label.label,
+              primary: label.is_primary,
+            })
+            .collect();
+        }
+
+        for child in &d.children {
+          match child.level {
+            Level::Help => message.help.push(child.message()),
+            _ => message.notes.push(child.message()),
+          }
         }
 
         message
@@ -45,16 +172,29 @@ impl DiagnosticBuffer {
 
     Self(diagnostics)
   }
+
+  pub fn is_empty(&self) -> bool {
+    self.0.is_empty()
+  }
+
+  /// Returns `true` if the buffer contains any error.
+  pub fn has_errors(&self) -> bool {
+    self.0.iter().any(|d| d.severity == Severity::Error)
+  }
 }
 
 /// A buffer for collecting errors from the AST parser.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct ErrorBuffer(Arc<RwLock<Vec<Diagnostic>>>);
 
 impl ErrorBuffer {
   pub fn new() -> Self {
     Self(Arc::new(RwLock::new(Vec::new())))
   }
+
+  pub fn is_empty(&self) -> bool {
+    self.0.read().unwrap().is_empty()
+  }
 }
 
 impl Emitter for ErrorBuffer {
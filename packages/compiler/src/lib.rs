@@ -1,10 +1,10 @@
 // Copyright 2020-2021 postUI Lab. All rights reserved. MIT license.
 
 pub mod ast;
+pub mod codeframe;
 pub mod compiler;
+pub mod error;
 pub mod resolve;
 
-mod error;
-
 #[macro_use]
 extern crate lazy_static;
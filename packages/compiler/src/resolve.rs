@@ -1,6 +1,8 @@
 // Copyright 2020-2021 postUI Lab. All rights reserved. MIT license.
 
+use crate::error::DiagnosticBuffer;
 use serde::Serialize;
+use swc_common::{BytePos, LineCol};
 use swc_ecmascript::parser::JscTarget;
 
 pub type Target = JscTarget;
@@ -20,6 +22,48 @@ pub struct CSSTemplate {
   pub exprs: Vec<String>,
 }
 
+impl CSSTemplate {
+  /// Append a template, its first quasi is joined to the last quasi of the existing one.
+  pub fn append(&mut self, template: CSSTemplate) {
+    let mut quasis = template.quasis.into_iter();
+    if let (Some(last), Some(first)) = (self.quasis.last_mut(), quasis.next()) {
+      last.push_str(&first);
+    }
+    self.quasis.extend(quasis);
+    self.exprs.extend(template.exprs);
+  }
+}
+
+/// The static stylesheet extracted from the component.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct ExtractedCSS {
+  pub code: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub map: Option<String>,
+  /// mappings from the code to the component source, used to build the `map`
+  #[serde(skip)]
+  pub mappings: Vec<(BytePos, LineCol)>,
+}
+
+impl ExtractedCSS {
+  /// Append a stylesheet, its mappings are moved after the existing lines.
+  pub fn append(&mut self, code: &str, mappings: Vec<(BytePos, LineCol)>) {
+    let lines = self.code.matches('\n').count() as u32;
+    self.code.push_str(code);
+    self
+      .mappings
+      .extend(mappings.into_iter().map(|(pos, LineCol { line, col })| {
+        (
+          pos,
+          LineCol {
+            line: line + lines,
+            col,
+          },
+        )
+      }));
+  }
+}
+
 /// A Resolver to resolve aleph.js import/export URL.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Resolver {
@@ -31,6 +75,10 @@ pub struct Resolver {
   pub dep_graph: Vec<DependencyDescriptor>,
   /// inline styles
   pub css: Option<CSSTemplate>,
+  /// static styles extracted in production
+  pub extracted_css: Option<ExtractedCSS>,
+  /// warnings of the transform
+  pub diagnostics: DiagnosticBuffer,
 }
 
 impl Resolver {
@@ -40,6 +88,8 @@ impl Resolver {
       runtime_module: runtime_module.into(),
       dep_graph: Vec::new(),
       css: None,
+      extracted_css: None,
+      diagnostics: DiagnosticBuffer::default(),
     }
   }
 }
@@ -51,6 +101,8 @@ impl Default for Resolver {
       runtime_module: "alef-dom".into(),
       dep_graph: Vec::new(),
       css: None,
+      extracted_css: None,
+      diagnostics: DiagnosticBuffer::default(),
     }
   }
 }
@@ -82,6 +134,17 @@ pub fn to_component_name(s: &str) -> String {
   char_vec.into_iter().collect()
 }
 
+/// Create the scoped class name of the component styles with the FNV-1a hash of the specifier,
+/// the result is stable between builds.
+pub fn to_style_id(specifier: &str) -> String {
+  let mut hash: u32 = 0x811c_9dc5;
+  for byte in specifier.bytes() {
+    hash ^= byte as u32;
+    hash = hash.wrapping_mul(0x0100_0193);
+  }
+  format!("alef-{:08x}", hash)
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -97,4 +160,11 @@ mod tests {
     assert_eq!(to_component_name("798.alef"), "App");
     assert_eq!(to_component_name("Hello 世界!.alef"), "Hello");
   }
+
+  #[test]
+  fn test_to_style_id() {
+    assert_eq!(to_style_id(""), "alef-811c9dc5");
+    assert_eq!(to_style_id("./App.alef"), to_style_id("./App.alef"));
+    assert_ne!(to_style_id("./App.alef"), to_style_id("./Logo.alef"));
+  }
 }
@@ -1,4 +1,4 @@
-use alef::compiler::Compiler;
+use alef::compiler::{Compiler, EmitOptions};
 use alef::resolve::Resolver; 
 use std::{cell::RefCell, rc::Rc};
 
@@ -6,7 +6,7 @@ pub fn t(specifer: &str, source: &str) -> (String, Rc<RefCell<Resolver>>) {
     let compiler = Compiler::parse(specifer, source).expect("could not parse module");
     let resolver = Rc::new(RefCell::new(Resolver::default()));
     let (code, _) = compiler
-        .transpile(resolver.clone())
+        .transpile(resolver.clone(), &EmitOptions::default())
         .expect("could not transpile module");
     println!("{}", code);
     (code, resolver)
@@ -20,7 +20,7 @@ pub fn t_custom_runtime_module(
     let compiler = Compiler::parse(specifer, source).expect("could not parse module");
     let resolver = Rc::new(RefCell::new(Resolver::new(specifer, runtime_module)));
     let (code, _) = compiler
-        .transpile(resolver.clone())
+        .transpile(resolver.clone(), &EmitOptions::default())
         .expect("could not transpile module");
     println!("{}", code);
     (code, resolver)
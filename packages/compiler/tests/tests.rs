@@ -1,7 +1,11 @@
 mod common;
 
+use alef::compiler::{Compiler, EmitOptions};
+use alef::error::{DiagnosticBuffer, Severity};
+use alef::resolve::{to_style_id, Resolver, Target};
 use common::{t, t_custom_runtime_module};
 use regex::Regex;
+use std::{cell::RefCell, rc::Rc};
 
 #[test]
 fn test_custom_runmtime_module() {
@@ -63,3 +67,1115 @@ fn test_component_dirty() {
   assert!(r1.is_match(code.as_str()));
   assert!(r2.is_match(code.as_str()));
 }
+
+#[test]
+fn test_component_condition() {
+  let source = r#"
+    let ok = false
+    let show = true
+
+    $t: if (ok) {
+      <p>OK</p>
+    } else if (show) {
+      <p>Loading...</p>
+    } else {
+      <button onClick={() => { ok = true }}>Retry</button>
+    }
+    $t: show ? <p>Shown</p> : null
+    $t: <div>{!ok && <span>Not OK</span>}</div>
+    $t: <footer>{DEBUG && <small>Debug</small>}</footer>
+  "#;
+  let (code, _) = t("App.alef", source);
+  assert!(code.contains("import { Component, Element, Dirty, IfElse, If } from \"alef-dom\";"));
+
+  let r1 = Regex::new(
+    r#"IfElse\(\[\s*\[\s*\(\)\s*=>\s*ok\s*,\s*\(\)\s*=>\s*Element\("p", null, "OK"\)\s*\],\s*\[\s*\(\)\s*=>\s*show\s*,\s*\(\)\s*=>\s*Element\("p", null, "Loading\.\.\."\)\s*\],\s*\[\s*\(\)\s*=>\s*true\s*,\s*\(\)\s*=>\s*Element\("button""#,
+  )
+  .unwrap();
+  let r2 = Regex::new(r"\],\s*\[\s*0,\s*1\s*\]\s*\)").unwrap();
+  let r3 = Regex::new(
+    r#"If\(\(\)\s*=>\s*show\s*,\s*\(\)\s*=>\s*Element\("p", null, "Shown"\)\s*,\s*\[\s*1\s*\]\s*\)"#,
+  )
+  .unwrap();
+  let r4 = Regex::new(
+    r#"Element\("div", null, If\(\(\)\s*=>\s*!ok\s*,\s*\(\)\s*=>\s*Element\("span", null, "Not OK"\)\s*,\s*\[\s*0\s*\]\s*\)\)"#,
+  )
+  .unwrap();
+  assert!(r1.is_match(code.as_str()));
+  assert!(r2.is_match(code.as_str()));
+  assert!(r3.is_match(code.as_str()));
+  let r5 = Regex::new(
+    r#"Element\("footer", null, If\(\(\)\s*=>\s*DEBUG\s*,\s*\(\)\s*=>\s*Element\("small", null, "Debug"\)\s*,\s*\[\s*\]\s*\)\)"#,
+  )
+  .unwrap();
+  assert!(r4.is_match(code.as_str()));
+  assert!(r5.is_match(code.as_str()));
+}
+
+#[test]
+fn test_component_list() {
+  let source = r#"
+    let todos: Array<{ id: number, title: string, completed: boolean }> = []
+    let filter = 'all'
+
+    $t: (
+      <ul>
+        {todos.filter(todo => filter === 'all' || !todo.completed).map(todo => (
+          <li key={todo.id}>{todo.title}</li>
+        ))}
+      </ul>
+    )
+    $t: <p>{['all', 'active'].map(function(name, index) { return <a>{index}. {name}</a> })}</p>
+  "#;
+  let (code, _) = t("App.alef", source);
+  assert!(code.contains("import { Component, Element, Memo, List } from \"alef-dom\";"));
+  assert!(!code.contains("key: Memo"));
+
+  let r1 = Regex::new(
+    r#"List\(\(\)\s*=>\s*todos\.filter\(\(todo\)\s*=>\s*filter\s*===\s*'all'\s*\|\|\s*!todo\.completed\s*\)\s*,\s*\(todo\)\s*=>\s*\(\{\s*key:\s*todo\.id,\s*create:\s*\(\)\s*=>\s*Element\("li", null, Memo\(\(\)\s*=>\s*todo\.title\s*,\s*\[\s*2\s*\]\s*\)\)\s*\}\)\s*,\s*\[\s*0,\s*1\s*\]\s*\)"#,
+  )
+  .unwrap();
+  let r2 = Regex::new(
+    r#"List\(\(\)\s*=>\s*\[\s*'all',\s*'active'\s*\]\s*,\s*\(name, index\)\s*=>\s*\(\{\s*create:\s*\(\)\s*=>\s*Element\("a", null, Memo\(\(\)\s*=>\s*index\s*,\s*\[\s*3\s*\]\s*\),\s*". ",\s*Memo\(\(\)\s*=>\s*name\s*,\s*\[\s*2\s*\]\s*\)\)\s*\}\)\s*,\s*\[\s*\]\s*\)"#,
+  )
+  .unwrap();
+  assert!(r1.is_match(code.as_str()));
+  assert!(r2.is_match(code.as_str()));
+}
+
+#[test]
+fn test_component_memo() {
+  let source = r#"
+    let n: number = 1
+
+    const double: Memo<number> = n * 2
+    $: quadruple = double * 2
+
+    $t: <p>{quadruple}</p>
+  "#;
+  let (code, _) = t("App.alef", source);
+  let r1 =
+    Regex::new(r"let double;\s*Memo\(\(\)\s*=>\s*double = n \* 2\s*,\s*\[\s*0\s*\]\s*\)").unwrap();
+  let r2 = Regex::new(
+    r"let quadruple;\s*Memo\(\(\)\s*=>\s*quadruple = double \* 2\s*,\s*\[\s*0,\s*1\s*\]\s*\)",
+  )
+  .unwrap();
+  let r3 = Regex::new(r"Memo\(\(\)\s*=>\s*quadruple\s*,\s*\[\s*0,\s*1,\s*2\s*\]\s*\)").unwrap();
+  assert!(r1.is_match(code.as_str()));
+  assert!(r2.is_match(code.as_str()));
+  assert!(r3.is_match(code.as_str()));
+}
+
+#[test]
+fn test_component_side_effect() {
+  let source = r#"
+    let n: number = 0
+    let title: string = 'Counter'
+
+    $: console.log(n)
+    $_title: {
+      document.title = title
+    }
+    $_timer: () => {
+      const timer = setInterval(() => n++, 1000)
+      return () => clearInterval(timer)
+    }
+  "#;
+  let (code, _) = t("App.alef", source);
+  let r1 = Regex::new(
+    r"this\.onMount\(Effect\(\(\)\s*=>\s*\{\s*console\.log\(n\);\s*\}\s*,\s*\[\s*0\s*\]\s*\)\);",
+  )
+  .unwrap();
+  let r2 = Regex::new(
+    r#"this\.onMount\(Effect\(\(\)\s*=>\s*\{\s*document\.title = title;\s*\}\s*,\s*\[\s*1\s*\]\s*,\s*"title"\s*\)\);"#,
+  )
+  .unwrap();
+  let r3 = Regex::new(
+    r#"this\.onMount\(Effect\(\(\)\s*=>\s*\{\s*const timer = setInterval[^;]+;\s*return \(\)\s*=>\s*clearInterval\(timer\)\s*;\s*\}\s*,\s*\[\s*0\s*\]\s*,\s*"timer"\s*\)\);"#,
+  )
+  .unwrap();
+  assert!(r1.is_match(code.as_str()));
+  assert!(r2.is_match(code.as_str()));
+  assert!(r3.is_match(code.as_str()));
+}
+
+#[test]
+fn test_component_prop() {
+  let source = r#"
+    const title: Prop<string> = 'Hello'
+    const { name, count = 0 }: Prop<{ name: string, count: number }> = {}
+
+    $t: <p>{title} {name}</p>
+  "#;
+  let (code, _) = t("Hello.alef", source);
+  let r1 = Regex::new(r"let \{\s*title\s*=\s*'Hello'\s*\} = props;").unwrap();
+  let r2 = Regex::new(
+    r#"this\.listen\("title", Dirty\(\(\)\s*=>\s*\{\s*\(\{\s*title\s*=\s*'Hello'\s*\} = this\.props\);\s*\}\s*,\s*\[\s*0\s*\]\s*\)\);"#,
+  )
+  .unwrap();
+  let r3 = Regex::new(r"let \{\s*name\s*,\s*count\s*=\s*0\s*\} = props;").unwrap();
+  let r4 = Regex::new(
+    r#"this\.listen\("count", Dirty\(\(\)\s*=>\s*\{\s*\(\{\s*count\s*=\s*0\s*\} = this\.props\);\s*\}\s*,\s*\[\s*2\s*\]\s*\)\);"#,
+  )
+  .unwrap();
+  assert!(r1.is_match(code.as_str()));
+  assert!(r2.is_match(code.as_str()));
+  assert!(r3.is_match(code.as_str()));
+  assert!(r4.is_match(code.as_str()));
+  // `Component.update` calls the listeners without arguments
+  assert!(!code.contains("(props) =>"));
+
+  let source = r#"
+    import Hello from './Hello.alef'
+
+    let name = 'World'
+
+    $t: <Hello title="Hi" name={name} />
+  "#;
+  let (code, _) = t("App.alef", source);
+  let r = Regex::new(
+    r#"New\(Hello, \{\s*title: "Hi",\s*name: Memo\(\(\)\s*=>\s*name\s*,\s*\[\s*0\s*\]\s*\)\s*\}\)"#,
+  )
+  .unwrap();
+  assert!(r.is_match(code.as_str()));
+}
+
+#[test]
+fn test_component_slots() {
+  let source = r#"
+    const slots: Prop<Slots>
+
+    $t: (
+      <header>
+        <slot name="icon"><img src="./logo.svg" /></slot>
+        {slots.title || <h1>Alef</h1>}
+        {slots}
+      </header>
+    )
+  "#;
+  let (code, _) = t("Logo.alef", source);
+  let r1 = Regex::new(r"let slots = props\.\$\$slots \|\| \{\s*\};").unwrap();
+  let r2 = Regex::new(
+    r#"Slot\(\(props\.\$\$slots \|\| \{\s*\}\)\.icon\s*,\s*\(\)\s*=>\s*Element\("img", \{\s*src: "\./logo\.svg"\s*\}\)\s*\)"#,
+  )
+  .unwrap();
+  let r3 = Regex::new(r#"Slot\(slots\.title\s*,\s*\(\)\s*=>\s*Element\("h1", null, "Alef"\)\s*\)"#)
+    .unwrap();
+  let r4 = Regex::new(r"Slot\(slots\)").unwrap();
+  assert!(r1.is_match(code.as_str()));
+  assert!(r2.is_match(code.as_str()));
+  assert!(r3.is_match(code.as_str()));
+  assert!(r4.is_match(code.as_str()));
+
+  let source = r#"
+    import Logo from './Logo.alef'
+
+    $t: (
+      <Logo>
+        <img slot="icon" src="./alef.svg" />
+        <span>Alef</span>
+      </Logo>
+    )
+  "#;
+  let (code, _) = t("App.alef", source);
+  let r = Regex::new(
+    r#"New\(Logo, null, \{\s*icon:\s*\(\)\s*=>\s*Element\("img", \{\s*src: "\./alef\.svg"\s*\}\)\s*,\s*default:\s*\(\)\s*=>\s*Element\("span", null, "Alef"\)\s*\}\s*\)"#,
+  )
+  .unwrap();
+  assert!(r.is_match(code.as_str()));
+}
+
+#[test]
+fn test_component_context() {
+  let source = r#"
+    let dark = false
+
+    const theme: Context<"theme", string> = dark ? 'dark' : 'light'
+  "#;
+  let (code, _) = t("App.alef", source);
+  let r1 = Regex::new(
+    r"let theme;\s*Memo\(\(\)\s*=>\s*theme = dark \? 'dark' : 'light'\s*,\s*\[\s*0\s*\]\s*\);",
+  )
+  .unwrap();
+  let r2 =
+    Regex::new(r#"this\.provide\("theme", Memo\(\(\)\s*=>\s*theme\s*,\s*\[\s*0,\s*1\s*\]\s*\)\);"#)
+      .unwrap();
+  assert!(r1.is_match(code.as_str()));
+  assert!(r2.is_match(code.as_str()));
+
+  let source = r#"
+    const theme: Context<"theme", string>
+
+    $t: <p className={theme}>Alef</p>
+  "#;
+  let (code, _) = t("Button.alef", source);
+  let r = Regex::new(
+    r#"let theme;\s*this\.consume\("theme", Dirty\(\(value\)\s*=>\s*\{\s*theme = value;\s*\}\s*,\s*\[\s*0\s*\]\s*\)\);"#,
+  )
+  .unwrap();
+  assert!(r.is_match(code.as_str()));
+}
+
+#[test]
+fn test_component_fc() {
+  let source = r#"
+    let items: string[] = ['a', 'b']
+
+    const Item: FC<{ title: string }> = ({ title }) => {
+      let done = false
+
+      return <li onClick={() => { done = !done }}>{title}</li>
+    }
+    const Empty: FC = () => <p>Empty</p>
+
+    $t: (
+      <ul>
+        {items.map(item => <Item key={item} title={item} />)}
+        <Empty />
+      </ul>
+    )
+  "#;
+  let (code, _) = t("App.alef", source);
+  assert!(code.contains("import { Component, Dirty, Element, Memo, New, List } from \"alef-dom\";"));
+
+  let r1 = Regex::new(
+    r#"class Item extends Component \{\s*constructor\(props\)\s*\{\s*super\(props\);\s*let \{\s*title\s*\} = props;"#,
+  )
+  .unwrap();
+  let r2 = Regex::new(r"let done = false;").unwrap();
+  let r3 = Regex::new(
+    r#"onClick: Dirty\(\(\)\s*=>\s*\{\s*done = !done;\s*\}\s*,\s*\[\s*1\s*\]\s*\)\s*\}\s*,\s*Memo\(\(\)\s*=>\s*title\s*,\s*\[\s*0\s*\]\s*\)"#,
+  )
+  .unwrap();
+  let r4 = Regex::new(r#"class Empty extends Component \{"#).unwrap();
+  let r5 =
+    Regex::new(r#"New\(Item, \{\s*title: Memo\(\(\)\s*=>\s*item\s*,\s*\[\s*1\s*\]\s*\)\s*\}\)"#)
+      .unwrap();
+  let r6 = Regex::new(r#"New\(Empty, null\)"#).unwrap();
+  assert!(r1.is_match(code.as_str()));
+  assert!(r2.is_match(code.as_str()));
+  assert!(r3.is_match(code.as_str()));
+  assert!(r4.is_match(code.as_str()));
+  assert!(r5.is_match(code.as_str()));
+  assert!(r6.is_match(code.as_str()));
+}
+
+#[test]
+fn test_component_style() {
+  let source = r#"
+    let color = 'red'
+    const size = 16
+
+    $t: <p>Hello World!</p>
+    $t: <button className="primary" onClick={() => { color = 'blue' }}>Blue</button>
+
+    $style: `
+      /* paragraph */
+      p {
+        color: ${color};
+        font-size: ${size}px;
+      }
+      h1 {
+        font-size: 200%;
+      }
+      :global(body) button.primary, h2.title {
+        font-weight: bold;
+      }
+      @media (max-width: 600px) {
+        p { font-size: 14px }
+      }
+    `
+  "#;
+  let (code, resolver) = t("App.alef", source);
+  let id = to_style_id("./App.alef");
+  assert!(code.contains("Style"));
+  assert!(Regex::new(&format!(
+    r#"const nodes = \[\s*Style\("{}",\s*\(\)\s*=>\s*`p\.{} \{{"#,
+    id, id
+  ))
+  .unwrap()
+  .is_match(&code));
+  assert!(Regex::new(&format!(
+    r#"\{{\s*"--{}-0": Memo\(\(\)\s*=>\s*color\s*,\s*\[\s*0\s*\]\s*\)\s*\}}\s*\),"#,
+    id
+  ))
+  .unwrap()
+  .is_match(&code));
+  assert!(code.contains(&format!("className: \"{}\"", id)));
+  assert!(code.contains(&format!("className: \"primary {}\"", id)));
+  let resolver = resolver.borrow();
+  let css = resolver.css.as_ref().unwrap();
+  assert_eq!(css.exprs, vec!["size".to_string()]);
+  assert_eq!(css.quasis.len(), 2);
+  assert_eq!(
+    css.quasis[0],
+    format!("p.{} {{\n  color: var(--{}-0);\n  font-size: ", id, id)
+  );
+  assert!(css.quasis[1].starts_with("px;\n}\n"));
+  assert!(!css.quasis[1].contains("h1"));
+  assert!(css.quasis[1].contains(&format!("body button.primary.{} {{\n", id)));
+  assert!(css.quasis[1].contains(&format!(
+    "@media (max-width: 600px) {{\n  p.{} {{\n    font-size: 14px;\n  }}\n}}\n",
+    id
+  )));
+  assert!(!css.quasis[1].contains("paragraph"));
+  let diagnostics = &resolver.diagnostics.0;
+  assert_eq!(diagnostics.len(), 2);
+  assert_eq!(diagnostics[0].severity, Severity::Warning);
+  assert_eq!(diagnostics[0].code.as_deref(), Some("A011"));
+  assert_eq!(diagnostics[0].message, "unused CSS selector `h1`");
+  assert_eq!(diagnostics[0].line, 14);
+  assert_eq!(diagnostics[1].message, "unused CSS selector `h2.title`");
+  // located at the rule of the dropped selector
+  assert_eq!(diagnostics[1].line, 17);
+  assert_eq!(diagnostics[1].col, 6);
+  assert!(!css.quasis[1].contains("h2"));
+
+  let compiler = Compiler::parse("App.alef", source).unwrap();
+  let resolver = Rc::new(RefCell::new(Resolver::default()));
+  let options = EmitOptions {
+    is_dev: true,
+    ..EmitOptions::default()
+  };
+  compiler.transpile(resolver.clone(), &options).unwrap();
+  let resolver = resolver.borrow();
+  let css = resolver.css.as_ref().unwrap();
+  assert!(css.quasis[1].contains("/* unused h1 */\n"));
+
+  // the styles of the inline components are kept with the styles of the module
+  let source = r#"
+    const size = 16
+
+    const Item: FC<{ title: string }> = ({ title }) => {
+      $style: `
+        li {
+          font-weight: bold;
+        }
+      `
+
+      return <li>{title}</li>
+    }
+
+    $t: <ul><Item title="a" /></ul>
+
+    $style: `
+      ul {
+        font-size: ${size}px;
+      }
+    `
+  "#;
+  let (_, resolver) = t("App.alef", source);
+  let resolver = resolver.borrow();
+  let css = resolver.css.as_ref().unwrap();
+  let code = css.quasis.join("");
+  assert!(code.contains("li."));
+  assert!(code.contains("ul."));
+  assert_eq!(css.exprs, vec!["size".to_string()]);
+
+  // the parse errors are located at the broken declaration
+  let source = r#"
+    $t: <p>Hi</p>
+
+    $style: `
+      p {
+        color red;
+      }
+    `
+  "#;
+  let compiler = Compiler::parse("App.alef", source).unwrap();
+  let err = compiler
+    .transpile(
+      Rc::new(RefCell::new(Resolver::default())),
+      &EmitOptions::default(),
+    )
+    .unwrap_err();
+  let diagnostics = err.downcast::<DiagnosticBuffer>().unwrap();
+  assert_eq!(diagnostics.0[0].code.as_deref(), Some("A010"));
+  assert_eq!(diagnostics.0[0].line, 6);
+  assert_eq!(diagnostics.0[0].col, 8);
+}
+
+#[test]
+fn test_component_extract_css() {
+  let source = r#"
+    $t: <p>Hello World!</p>
+
+    $style: `
+      p {
+        color: red;
+      }
+    `
+  "#;
+  let compiler = Compiler::parse("App.alef", source).unwrap();
+  let resolver = Rc::new(RefCell::new(Resolver::default()));
+  let options = EmitOptions {
+    extract_css: true,
+    ..EmitOptions::default()
+  };
+  let (code, _) = compiler.transpile(resolver.clone(), &options).unwrap();
+  let id = to_style_id("./App.alef");
+  assert!(!code.contains("Style"));
+  assert!(code.contains(&format!("className: \"{}\"", id)));
+  let resolver = resolver.borrow();
+  assert!(resolver.css.is_none());
+  let css = resolver.extracted_css.as_ref().unwrap();
+  assert_eq!(css.code, format!("p.{} {{\n  color: red;\n}}\n", id));
+  assert_eq!(css.mappings.len(), 1);
+  assert_eq!(css.mappings[0].0 .0 as usize, source.find("p {").unwrap());
+  assert_eq!(css.mappings[0].1.line, 0);
+  let map = css.map.as_ref().unwrap();
+  assert!(map.contains("\"sources\":[\"App.alef\"]"));
+
+  let source = r#"
+    let color = 'red'
+
+    $t: <p onClick={() => { color = 'blue' }}>Hello World!</p>
+
+    $style: `
+      p {
+        color: ${color};
+      }
+    `
+  "#;
+  let compiler = Compiler::parse("App.alef", source).unwrap();
+  let resolver = Rc::new(RefCell::new(Resolver::default()));
+  let (code, _) = compiler.transpile(resolver.clone(), &options).unwrap();
+  assert!(code.contains("Style"));
+  assert!(resolver.borrow().extracted_css.is_none());
+}
+
+#[test]
+fn test_component_async() {
+  let source = r#"
+    let data = await fetch('/api/todos').then(res => res.json())
+
+    $t: <ul>{data.todos.map(todo => <li>{todo}</li>)}</ul>
+    $t_pending: <p>Loading...</p>
+    $t_rejected: <p>Failed to load</p>
+  "#;
+  let (code, resolver) = t("App.alef", source);
+  assert!(resolver.borrow().diagnostics.is_empty());
+  assert!(code.contains("let data;"));
+  assert!(!code.contains("= await"));
+  let r = Regex::new(
+    r#"this\.await\(fetch\('/api/todos'\)\.then\(\(res\)\s*=>\s*res\.json\(\)\s*\),\s*Dirty\(\(value\)\s*=>\s*\{\s*data = value;\s*\},\s*\[\s*0\s*\]\)\);"#,
+  )
+  .unwrap();
+  assert!(r.is_match(&code));
+  assert!(code.contains("this.fallback(\"pending\", Element(\"p\", null, \"Loading...\"));"));
+  assert!(code.contains("this.fallback(\"rejected\", Element(\"p\", null, \"Failed to load\"));"));
+
+  let source = r#"
+    let data = []
+
+    $t: <p>{data.length}</p>
+    $t_pending: <p>Loading...</p>
+  "#;
+  let (_, resolver) = t("App.alef", source);
+  let resolver = resolver.borrow();
+  assert_eq!(resolver.diagnostics.0.len(), 1);
+  assert_eq!(resolver.diagnostics.0[0].code.as_deref(), Some("A012"));
+}
+
+#[test]
+fn test_component_catch() {
+  let source = r#"
+    import Profile from './Profile.alef'
+
+    let n = 0
+
+    $t: <Profile n={n} />
+    $catch: (error: Error, reset) => <p>{error.message} <button onClick={reset}>Retry</button></p>
+  "#;
+  let (code, resolver) = t("App.alef", source);
+  assert!(resolver.borrow().diagnostics.is_empty());
+  let r = Regex::new(
+    r#"super\(props\);\s*this\.catch\(\(error, reset\)\s*=>\s*Element\("p", null, error\.message, " ", Element\("button", \{\s*onClick: reset\s*\}, "Retry"\)\)\s*\);\s*let n = 0;"#,
+  )
+  .unwrap();
+  assert!(r.is_match(&code));
+
+  let compiler = Compiler::parse("App.alef", source).unwrap();
+  let options = EmitOptions {
+    ssr: true,
+    ..EmitOptions::default()
+  };
+  let (code, _) = compiler
+    .transpile(Rc::new(RefCell::new(Resolver::default())), &options)
+    .unwrap();
+  assert!(code.contains("try {\n        let n = 0;"));
+  let r = Regex::new(
+    r#"\} catch \(error\) \{\s*const reset = \(\)\s*=>\s*\{\s*\};\s*return "<p><!--t-->" \+ escape\(error\.message\) \+ " <button>Retry</button></p>";"#,
+  )
+  .unwrap();
+  assert!(r.is_match(&code));
+
+  let source = r#"
+    $t: <p>Hello</p>
+    $catch: console.log('error')
+  "#;
+  let compiler = Compiler::parse("App.alef", source).unwrap();
+  let err = compiler
+    .transpile(
+      Rc::new(RefCell::new(Resolver::default())),
+      &EmitOptions::default(),
+    )
+    .unwrap_err();
+  let diagnostics = err.downcast::<DiagnosticBuffer>().unwrap();
+  assert_eq!(diagnostics.0[0].code.as_deref(), Some("A013"));
+
+  let source = r#"
+    $t: <p>Hello</p>
+    $catch: <p>Oops</p>
+    $catch: error => <p>{error.message}</p>
+  "#;
+  let compiler = Compiler::parse("App.alef", source).unwrap();
+  let err = compiler
+    .transpile(
+      Rc::new(RefCell::new(Resolver::default())),
+      &EmitOptions::default(),
+    )
+    .unwrap_err();
+  let diagnostics = err.downcast::<DiagnosticBuffer>().unwrap();
+  assert_eq!(diagnostics.0.len(), 1);
+  assert_eq!(diagnostics.0[0].code.as_deref(), Some("A013"));
+  assert_eq!(diagnostics.0[0].line, 4);
+}
+
+#[test]
+fn test_component_ssr() {
+  let source = r#"
+    import Logo from './Logo.alef'
+
+    let name: string = 'World'
+    let todos = ['a', 'b']
+    const title: Prop<string> = 'Hi'
+    const theme: Context<'theme'> = 'dark'
+
+    $: document.title = name
+
+    $t: <div className="app" hidden>
+      <h1 title={title} onClick={() => { name = 'alef' }}>Hello {name} & you!</h1>
+      <img src="/logo.png" />
+      {name === 'World' && <p>Default</p>}
+      <ul>{todos.map(todo => <li key={todo}>{todo}</li>)}</ul>
+      <Logo size={24}><span slot="text">Alef</span></Logo>
+    </div>
+
+    $style: `
+      h1 {
+        color: red;
+      }
+    `
+  "#;
+  let compiler = Compiler::parse("App.alef", source).unwrap();
+  let resolver = Rc::new(RefCell::new(Resolver::default()));
+  let options = EmitOptions {
+    ssr: true,
+    ..EmitOptions::default()
+  };
+  let (code, _) = compiler.transpile(resolver.clone(), &options).unwrap();
+  let id = to_style_id("./App.alef");
+  assert!(code.contains("export default function App(props = {"));
+  assert!(!code.contains("Component"));
+  assert!(!code.contains("Element"));
+  assert!(!code.contains("document.title"));
+  assert!(code.contains("import { attr, escape } from \"alef-dom\""));
+  assert!(code.contains(&format!(
+    "return \"<style id=\\\"{0}\\\">h1.{0} {{\\n  color: red;\\n}}\\n</style><div class=\\\"app\\\" hidden><h1\" + attr(\"title\", title) + \" class=\\\"{0}\\\">Hello <!--t-->\" + escape(name) + \" &amp; you!</h1><img src=\\\"/logo.png\\\"><!--if-->\"",
+    id
+  )));
+  assert!(
+    code.contains("(name === 'World' ? \"<p>Default</p>\" : \"\") + \"<!--/if--><ul><!--list-->\"")
+  );
+  assert!(Regex::new(
+    r#"todos\.map\(\(todo\)\s*=>\s*"<li><!--t-->"\s*\+\s*escape\(todo\)\s*\+\s*"</li>"\s*\)\.join\(""\)\s*\+\s*"<!--/list--></ul><!--c-->"\s*\+\s*Logo\("#
+  )
+  .unwrap()
+  .is_match(&code));
+  assert!(Regex::new(
+    r#"Logo\(\{\s*size: 24,\s*\$\$slots: \{\s*text: \(\)\s*=>\s*"<span>Alef</span>""#
+  )
+  .unwrap()
+  .is_match(&code));
+  assert!(Regex::new(
+    r#"\$\$contexts: Object\.assign\(\{\s*\}, props\.\$\$contexts, \{\s*"theme": theme\s*\}\)"#
+  )
+  .unwrap()
+  .is_match(&code));
+  assert!(resolver.borrow().css.is_some());
+
+  // only the imported and the `FC` components are rendered as components, like the DOM output
+  let source = r#"
+    $t: <Widget>Hi</Widget>
+  "#;
+  let compiler = Compiler::parse("App.alef", source).unwrap();
+  let (code, _) = compiler
+    .transpile(Rc::new(RefCell::new(Resolver::default())), &options)
+    .unwrap();
+  assert!(code.contains("return \"<Widget>Hi</Widget>\""));
+
+  // the interpolated style values can't close the `<style>` element
+  let source = r#"
+    let color = 'red'
+
+    $t: <p>Hi</p>
+
+    $style: `
+      p {
+        color: ${color};
+      }
+    `
+  "#;
+  let compiler = Compiler::parse("App.alef", source).unwrap();
+  let (code, _) = compiler
+    .transpile(Rc::new(RefCell::new(Resolver::default())), &options)
+    .unwrap();
+  assert!(code.contains("import { escapeStyle } from \"alef-dom\""));
+  assert!(code.contains("color: ${escapeStyle(color)};"));
+
+  let source = r#"
+    let items = []
+
+    $t: <p>{...items}</p>
+  "#;
+  let compiler = Compiler::parse("App.alef", source).unwrap();
+  let err = compiler
+    .clone()
+    .transpile(Rc::new(RefCell::new(Resolver::default())), &options)
+    .unwrap_err();
+  let diagnostics = err.downcast::<DiagnosticBuffer>().unwrap();
+  assert!(diagnostics.0[0].message.contains("JSX spread children"));
+  assert_eq!(diagnostics.0[0].code.as_deref(), Some("A015"));
+
+  // the DOM output reports the same error
+  let err = compiler
+    .transpile(
+      Rc::new(RefCell::new(Resolver::default())),
+      &EmitOptions::default(),
+    )
+    .unwrap_err();
+  let diagnostics = err.downcast::<DiagnosticBuffer>().unwrap();
+  assert_eq!(diagnostics.0[0].code.as_deref(), Some("A015"));
+}
+
+#[test]
+fn test_component_ssr_async() {
+  let source = r#"
+    let name = 'World'
+    let data = await fetch('/api/todos').then(res => res.json())
+    const count = data.todos.length
+
+    $t: <div>
+      <h1>Hello {name}</h1>
+      <p>{count} todos</p>
+      <ul>{data.todos.map(todo => <li>{todo}</li>)}</ul>
+    </div>
+  "#;
+  let compiler = Compiler::parse("App.alef", source).unwrap();
+  let resolver = Rc::new(RefCell::new(Resolver::default()));
+  let options = EmitOptions {
+    ssr: true,
+    ..EmitOptions::default()
+  };
+  let (code, _) = compiler.transpile(resolver.clone(), &options).unwrap();
+  assert!(code.contains(
+    "let name = 'World';\n    const asyncId = suspend(props, async function*(asyncId) {"
+  ));
+  assert!(code.contains("let data = await fetch('/api/todos')"));
+  assert!(code.contains("const count = data.todos.length;\n        yield resolveAsync(asyncId, 0, \"<!--t-->\" + escape(count));"));
+  assert!(code.contains("yield resolveAsync(asyncId, 1, \"<!--list-->\" + data.todos.map("));
+  assert!(code.contains(
+    "return \"<div><h1>Hello <!--t-->\" + escape(name) + \"</h1><p>\" + asyncPlaceholder(asyncId, 0) + \" todos</p><ul>\" + asyncPlaceholder(asyncId, 1) + \"</ul></div>\";"
+  ));
+}
+
+#[test]
+fn test_component_hydrate() {
+  let source = r#"
+    import Logo from './Logo.alef'
+
+    let n = 0
+    let todos = ['a', 'b']
+
+    $t: <div>
+      <p onClick={() => { n++ }}>Count: {n}</p>
+      {n > 0 && <span>positive</span>}
+      <ul>{todos.map(todo => <li key={todo}>{todo}</li>)}</ul>
+      <Logo size={n} />
+      <slot name="footer" />
+    </div>
+  "#;
+  let compiler = Compiler::parse("App.alef", source).unwrap();
+  let mut options = EmitOptions {
+    hydrate: true,
+    ..EmitOptions::default()
+  };
+  let (code, _) = compiler
+    .clone()
+    .transpile(Rc::new(RefCell::new(Resolver::default())), &options)
+    .unwrap();
+  assert!(!code.contains("Element"));
+  assert!(code.contains("cursor.text(\"Count: \")"));
+  assert!(code.contains("this.hydrate(nodes)"));
+  assert!(!code.contains("this.register"));
+  let r1 = Regex::new(
+    r#"const nodes = \(cursor\)\s*=>\s*\[\s*cursor\.element\("div", null, \(cursor\)\s*=>\s*\["#,
+  )
+  .unwrap();
+  let r2 = Regex::new(
+    r#"cursor\.element\("p", \{\s*onClick: Dirty\([\s\S]*?\}\s*, \(cursor\)\s*=>\s*\[\s*cursor\.text\("Count: "\),\s*cursor\.text\(Memo\(\(\)\s*=>\s*n\s*,"#,
+  )
+  .unwrap();
+  let r3 = Regex::new(
+    r#"cursor\.block\("if", If\(\(\)\s*=>\s*n > 0\s*, \(cursor\)\s*=>\s*cursor\.element\("span", null, \(cursor\)\s*=>\s*\[\s*cursor\.text\("positive"\)\s*\]\s*\)"#,
+  )
+  .unwrap();
+  let r4 = Regex::new(
+    r#"cursor\.block\("list", List\(\(\)\s*=>\s*todos\s*, \(todo\)\s*=>\s*\(\{\s*key: todo,\s*create: \(cursor\)\s*=>\s*cursor\.element\("li", null, \(cursor\)\s*=>\s*\[\s*cursor\.text\("#,
+  )
+  .unwrap();
+  let r5 = Regex::new(r#"cursor\.block\("c", New\(Logo, \{"#).unwrap();
+  let r6 = Regex::new(r#"cursor\.block\("s", Slot\("#).unwrap();
+  assert!(r1.is_match(code.as_str()));
+  assert!(r2.is_match(code.as_str()));
+  assert!(r3.is_match(code.as_str()));
+  assert!(r4.is_match(code.as_str()));
+  assert!(r5.is_match(code.as_str()));
+  assert!(r6.is_match(code.as_str()));
+
+  // the server renders the markers the client walks
+  options.hydrate = false;
+  options.ssr = true;
+  let (code, _) = compiler
+    .clone()
+    .transpile(Rc::new(RefCell::new(Resolver::default())), &options)
+    .unwrap();
+  assert!(code.contains("<p>Count: <!--t-->"));
+  assert!(code.contains("<!--if-->"));
+  assert!(code.contains("<ul><!--list-->"));
+  assert!(code.contains("<!--c-->"));
+  assert!(code.contains("<!--s-->\" + renderSlot("));
+
+  // the claims are located in development mode to report the hydration mismatches
+  options.ssr = false;
+  options.hydrate = true;
+  options.is_dev = true;
+  let (code, _) = compiler
+    .transpile(Rc::new(RefCell::new(Resolver::default())), &options)
+    .unwrap();
+  assert!(code.contains("cursor.at(\"./App.alef:8:7\").element(\"p\""));
+  assert!(!code.contains("Source("));
+}
+
+#[test]
+fn test_component_transition() {
+  let source = r#"
+    import { fade, fly } from 'alef-dom/transition'
+
+    let show = true
+    let y = 20
+    let items = [1, 2, 3]
+
+    $t: <>
+      {show && <p transition:fade>Hello</p>}
+      {items.map(item => <li key={item} in:fly={{ y }} out:fade={{ duration: 200 }}>{item}</li>)}
+    </>
+  "#;
+  let (code, resolver) = t("App.alef", source);
+  assert!(resolver.borrow().diagnostics.is_empty());
+  assert!(code.contains("import { Component, Fragment, Element, Transition, If, Memo, List }"));
+  let r = Regex::new(
+    r#"\(\)\s*=>\s*Transition\(Element\("p", null, "Hello"\), \{\s*transition: \[\s*fade\s*\]\s*\}\)"#,
+  )
+  .unwrap();
+  assert!(r.is_match(&code));
+  let r = Regex::new(
+    r#"create: \(\)\s*=>\s*Transition\(Element\("li", null, Memo\(\(\)\s*=>\s*item\s*, \[\s*3\s*\]\)\), \{\s*in: \[\s*fly,\s*Memo\(\(\)\s*=>\s*\(\{\s*y\s*\}\)\s*, \[\s*1\s*\]\)\s*\],\s*out: \[\s*fade,\s*\{\s*duration: 200\s*\}\s*\]\s*\}\)"#,
+  )
+  .unwrap();
+  assert!(r.is_match(&code));
+
+  let compiler = Compiler::parse("App.alef", source).unwrap();
+  let options = EmitOptions {
+    ssr: true,
+    ..EmitOptions::default()
+  };
+  let (code, _) = compiler
+    .transpile(Rc::new(RefCell::new(Resolver::default())), &options)
+    .unwrap();
+  assert!(code.contains(r#"(show ? "<p>Hello</p>" : "")"#));
+  assert!(code.contains(r#""<li><!--t-->" + escape(item) + "</li>""#));
+
+  let source = r#"
+    import { fade } from 'alef-dom/transition'
+
+    $t: <p transition:fade in:fade>Hello</p>
+  "#;
+  let compiler = Compiler::parse("App.alef", source).unwrap();
+  let err = compiler
+    .transpile(
+      Rc::new(RefCell::new(Resolver::default())),
+      &EmitOptions::default(),
+    )
+    .unwrap_err();
+  let diagnostics = err.downcast::<DiagnosticBuffer>().unwrap();
+  assert_eq!(diagnostics.0[0].code.as_deref(), Some("A016"));
+  assert_eq!(
+    diagnostics.0[0].message,
+    "duplicate transition directive `in:fade`"
+  );
+}
+
+#[test]
+fn test_component_hot_refresh() {
+  let source = r#"
+    let n = 0
+    let name: string
+    let { x, y } = { x: 1, y: 2 }
+
+    const Counter: FC = () => {
+      let count = 0
+
+      $t: <button onClick={() => { count++ }}>{count}</button>
+    }
+
+    $t: <div><p>{name} {n} {x}</p><Counter /></div>
+  "#;
+  let compiler = Compiler::parse("App.alef", source).unwrap();
+  let options = EmitOptions {
+    hot_refresh: true,
+    ..EmitOptions::default()
+  };
+  let (code, _) = compiler
+    .clone()
+    .transpile(Rc::new(RefCell::new(Resolver::default())), &options)
+    .unwrap();
+  assert!(code.contains("import { Component, Refresh,"));
+  let r = Regex::new(
+    r#"super\(props\);\s*const state = this\.restore\(\);\s*let n = "n" in state \? state\.n : 0;\s*let name = "name" in state \? state\.name : undefined;\s*let \{ x , y  \} = \{"#,
+  )
+  .unwrap();
+  assert!(r.is_match(&code));
+  assert!(code.contains(r#"let count = "count" in state2 ? state2.count : 0;"#));
+  let r = Regex::new(r#"this\.snapshot\(\(\)\s*=>\s*\(\{\s*count\s*\}\)\s*\);"#).unwrap();
+  assert!(r.is_match(&code));
+  // the inline component is registered with the component in the module scope
+  assert!(!code.contains("Refresh(Counter"));
+  let r = Regex::new(r#"this\.snapshot\(\(\)\s*=>\s*\(\{\s*n,\s*name\s*\}\)\s*\);"#).unwrap();
+  assert!(r.is_match(&code));
+  let r = Regex::new(
+    r#"\n\};\nRefresh\(App, "\./App\.alef", \[\s*"Counter"\s*\]\);\nif \(import\.meta\.hot\) \{\s*import\.meta\.hot\.accept\(\(mod\)\s*=>\s*\{\s*if \(mod\) \{\s*Refresh\.update\(mod\.default, "\./App\.alef"\);\s*\}\s*\}\);\s*\}"#,
+  )
+  .unwrap();
+  assert!(r.is_match(&code));
+
+  let options = EmitOptions {
+    hot_refresh: true,
+    ssr: true,
+    ..EmitOptions::default()
+  };
+  let (code, _) = compiler
+    .transpile(Rc::new(RefCell::new(Resolver::default())), &options)
+    .unwrap();
+  assert!(!code.contains("Refresh"));
+  assert!(!code.contains("import.meta.hot"));
+}
+
+#[test]
+fn test_component_dev() {
+  let source = r#"
+    const title: Prop<string | null> = 'Hello'
+    const { size, onClose, items = [] }: Prop<{ size?: 'sm' | 'lg', onClose: () => void, items: string[], data: any }> = {}
+
+    const Item: FC = () => <li>Item</li>
+
+    $t: <div>
+      <h1>{title}</h1>
+      {items.map(item => <p>{item}</p>)}
+      <Item />
+    </div>
+  "#;
+  let compiler = Compiler::parse("App.alef", source).unwrap();
+  let resolver = Rc::new(RefCell::new(Resolver::default()));
+  let options = EmitOptions {
+    is_dev: true,
+    ..EmitOptions::default()
+  };
+  let (code, _) = compiler
+    .clone()
+    .transpile(resolver.clone(), &options)
+    .unwrap();
+  let r =
+    Regex::new(r#"this\.assertProps\(\{\s*title: "null\|string\|undefined"\s*\}\);"#).unwrap();
+  assert!(r.is_match(&code));
+  let r = Regex::new(
+    r#"this\.assertProps\(\{\s*size: "string\|undefined",\s*onClose: "function",\s*items: "array\|undefined"\s*\}\);"#,
+  )
+  .unwrap();
+  assert!(r.is_match(&code));
+  assert!(code.contains(r#"Source(Element("li", null, "Item"), "./App.alef:5:28")"#));
+  assert!(code.contains(r#"Item.displayName = "Item";"#));
+  let r = Regex::new(r#"Source\(Element\("h1", null, Memo\(\(\)\s*=>\s*title\s*, \[\s*0\s*\]\)\), "\./App\.alef:8:7"\)"#).unwrap();
+  assert!(r.is_match(&code));
+  assert!(code.contains(r#"New(Item, null)), "./App.alef:7:9")"#));
+  assert!(code.contains("};\nApp.displayName = \"App\";"));
+  let resolver = resolver.borrow();
+  assert_eq!(resolver.diagnostics.0.len(), 1);
+  assert_eq!(resolver.diagnostics.0[0].code.as_deref(), Some("A014"));
+  assert_eq!(resolver.diagnostics.0[0].line, 9);
+
+  let resolver = Rc::new(RefCell::new(Resolver::default()));
+  let (code, _) = compiler
+    .transpile(resolver.clone(), &EmitOptions::default())
+    .unwrap();
+  assert!(!code.contains("assertProps"));
+  assert!(!code.contains("Source"));
+  assert!(!code.contains("displayName"));
+  assert!(resolver.borrow().diagnostics.is_empty());
+}
+
+#[test]
+fn test_target() {
+  let source = r#"
+    const user: Prop<{ name: string }> = null
+    let name = user?.name ?? 'guest'
+    let copy = { ...user }
+
+    class Store {
+      count = 0
+    }
+
+    async function load() {
+      try {
+        const res = await fetch('/api/user')
+        name = (await res.json()).name
+      } catch {
+        name = 'guest'
+      }
+    }
+
+    $t: <p onClick={load}>{name ** 2}</p>
+  "#;
+  let compiler = Compiler::parse("App.alef", source).unwrap();
+  let transpile = |target: Target| {
+    let options = EmitOptions {
+      target,
+      ..EmitOptions::default()
+    };
+    let (code, _) = compiler
+      .clone()
+      .transpile(Rc::new(RefCell::new(Resolver::default())), &options)
+      .unwrap();
+    code
+  };
+
+  let code = transpile(Target::Es2020);
+  assert!(code.contains("user?.name ?? 'guest'"));
+  assert!(code.contains("count = 0;"));
+  assert!(code.contains("async function load()"));
+
+  // optional chaining, nullish coalescing and class fields
+  let code = transpile(Target::Es2019);
+  assert!(!code.contains("?."));
+  assert!(!code.contains("??"));
+  assert!(code.contains("user === null || user === void 0 ? void 0 : user.name"));
+  assert!(code.contains("_defineProperty(this, \"count\", 0);"));
+  let r = Regex::new(r"let copy = \{\s*\.\.\.user\s*\};").unwrap();
+  assert!(r.is_match(&code));
+  assert!(code.contains("class App extends Component"));
+  assert!(code.contains("super(props);"));
+  assert!(code.contains("this.listen(\"user\""));
+  assert!(code.contains("this.register(nodes);"));
+  // optional catch binding of es2019 is kept
+  let r = Regex::new(r"\} catch\s*\{").unwrap();
+  assert!(r.is_match(&code));
+
+  // object rest spread and optional catch binding
+  let code = transpile(Target::Es2017);
+  let r = Regex::new(r"let copy = _objectSpread\(\{\s*\}, user\);").unwrap();
+  assert!(r.is_match(&code));
+  let r = Regex::new(r"\} catch \(\w+\)\s*\{").unwrap();
+  assert!(r.is_match(&code));
+  assert!(code.contains("async function load()"));
+
+  // async/await to generators
+  let code = transpile(Target::Es2016);
+  assert!(!code.contains("async function"));
+  assert!(code.contains("_asyncToGenerator(function*() {"));
+  assert!(code.contains("const res = yield fetch('/api/user');"));
+  assert!(code.contains("name ** 2"));
+
+  // exponentiation operator
+  let code = transpile(Target::Es2015);
+  assert!(code.contains("Math.pow(name, 2)"));
+  assert!(code.contains("class App extends Component"));
+
+  // classes, arrow functions and block scoping
+  let code = transpile(Target::Es5);
+  assert!(!code.contains("class App"));
+  assert!(!code.contains("=>"));
+  assert!(!code.contains("let "));
+  assert!(code.contains("_classCallCheck(this, App);"));
+  assert!(code.contains("export { App as default };"));
+}
+
+#[test]
+fn test_parse_error() {
+  let source = "let n = 0\n\n$t: <p>{n</p>\n";
+  let diagnostics = match Compiler::parse("App.alef", source) {
+    Ok(_) => panic!("parse should fail"),
+    Err(diagnostics) => diagnostics,
+  };
+  assert!(diagnostics.has_errors());
+  let d = &diagnostics.0[0];
+  assert_eq!(d.severity, Severity::Error);
+  assert_eq!(d.file, "App.alef");
+  assert_eq!(d.line, 3);
+  assert!(d.span.start >= 11 && d.span.end >= d.span.start);
+  assert!(diagnostics.to_string().contains(" at App.alef:3:"));
+}
+
+#[test]
+fn test_validation() {
+  let source = r#"
+    const double: Memo<number>
+
+    $style: 'p { color: red }'
+    $foo: console.log('foo')
+  "#;
+  let compiler = Compiler::parse("App.alef", source).unwrap();
+  let resolver = Rc::new(RefCell::new(Resolver::default()));
+  let err = compiler
+    .transpile(resolver, &EmitOptions::default())
+    .unwrap_err();
+  let diagnostics = err.downcast::<DiagnosticBuffer>().unwrap();
+  let codes: Vec<(Severity, &str)> = diagnostics
+    .0
+    .iter()
+    .map(|d| (d.severity, d.code.as_deref().unwrap()))
+    .collect();
+  assert_eq!(
+    codes,
+    vec![
+      (Severity::Error, "A005"),
+      (Severity::Error, "A002"),
+      (Severity::Warning, "A003")
+    ]
+  );
+  assert_eq!(diagnostics.0[0].line, 2);
+
+  let source = r#"
+    $foo: console.log('foo')
+  "#;
+  let (_, resolver) = t("App.alef", source);
+  assert_eq!(resolver.borrow().diagnostics.0.len(), 1);
+  let compiler = Compiler::parse("App.alef", source).unwrap();
+  let resolver = Rc::new(RefCell::new(Resolver::default()));
+  let options = EmitOptions {
+    ignore_warnings: vec!["A003".into()],
+    ..EmitOptions::default()
+  };
+  compiler.transpile(resolver.clone(), &options).unwrap();
+  assert!(resolver.borrow().diagnostics.is_empty());
+}
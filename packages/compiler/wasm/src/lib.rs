@@ -1,5 +1,7 @@
-use alef::compiler::Compiler;
-use alef::resolve::{CSSTemplate, DependencyDescriptor, Resolver, Target};
+use alef::codeframe::{render_code_frames, FrameStyle};
+use alef::compiler::{Compiler, EmitOptions};
+use alef::error::DiagnosticBuffer;
+use alef::resolve::{CSSTemplate, DependencyDescriptor, ExtractedCSS, Resolver, Target};
 use serde::{Deserialize, Serialize};
 use std::{cell::RefCell, rc::Rc};
 use wasm_bindgen::prelude::{wasm_bindgen, JsValue};
@@ -7,8 +9,8 @@ use wasm_bindgen::prelude::{wasm_bindgen, JsValue};
 #[derive(Clone, Debug, Default, Deserialize)]
 #[serde(deny_unknown_fields, rename_all = "camelCase")]
 pub struct CompileOptions {
-  #[serde(default = "default_runtime_module")]
-  pub runtime_module: String,
+  #[serde(default)]
+  pub runtime_module: Option<String>,
 
   #[serde(default = "default_target")]
   pub target: Target,
@@ -18,10 +20,26 @@ pub struct CompileOptions {
 
   #[serde(default)]
   pub hot_refresh: bool,
+
+  #[serde(default)]
+  pub ignore_warnings: Vec<String>,
+
+  #[serde(default)]
+  pub extract_css: bool,
+
+  #[serde(default)]
+  pub ssr: bool,
+
+  #[serde(default)]
+  pub hydrate: bool,
 }
 
-fn default_runtime_module() -> String {
-  "alef-dom".into()
+fn default_runtime_module(ssr: bool) -> String {
+  if ssr {
+    "alef-ssr".into()
+  } else {
+    "alef-dom".into()
+  }
 }
 
 fn default_target() -> Target {
@@ -36,7 +54,11 @@ pub struct TransformOutput {
   #[serde(skip_serializing_if = "Option::is_none")]
   pub css: Option<CSSTemplate>,
   #[serde(skip_serializing_if = "Option::is_none")]
+  pub extracted_css: Option<ExtractedCSS>,
+  #[serde(skip_serializing_if = "Option::is_none")]
   pub map: Option<String>,
+  #[serde(skip_serializing_if = "DiagnosticBuffer::is_empty")]
+  pub diagnostics: DiagnosticBuffer,
 }
 
 #[wasm_bindgen(js_name = "transformSync")]
@@ -45,16 +67,34 @@ pub fn transform_sync(specifier: &str, source: &str, opts: JsValue) -> Result<Js
 
   let opts: CompileOptions = opts
     .into_serde()
-    .map_err(|err| format!("failed to parse options: {}", err))
-    .unwrap();
+    .map_err(|err| format!("failed to parse options: {}", err))?;
+  let ssr = opts.ssr;
+  let runtime_module = opts
+    .runtime_module
+    .unwrap_or_else(|| default_runtime_module(ssr));
   let resolver = Rc::new(RefCell::new(Resolver::new(
     specifier,
-    opts.runtime_module.as_str(),
+    runtime_module.as_str(),
   )));
-  let compiler = Compiler::parse(specifier, source).expect("could not parse module");
+  let compiler = Compiler::parse(specifier, source)
+    .map_err(|diagnostics| JsValue::from_serde(&diagnostics).unwrap())?;
   let (code, map) = compiler
-    .transpile(resolver.clone())
-    .expect("could not transpile module");
+    .transpile(
+      resolver.clone(),
+      &EmitOptions {
+        target: opts.target,
+        ignore_warnings: opts.ignore_warnings,
+        is_dev: opts.is_dev,
+        extract_css: opts.extract_css,
+        ssr: opts.ssr,
+        hydrate: opts.hydrate,
+        hot_refresh: opts.hot_refresh,
+      },
+    )
+    .map_err(|err| match err.downcast::<DiagnosticBuffer>() {
+      Ok(diagnostics) => JsValue::from_serde(&diagnostics).unwrap(),
+      Err(err) => format!("could not transpile module: {}", err).into(),
+    })?;
   let r = resolver.borrow_mut();
   Ok(
     JsValue::from_serde(&TransformOutput {
@@ -62,7 +102,27 @@ pub fn transform_sync(specifier: &str, source: &str, opts: JsValue) -> Result<Js
       map,
       deps: r.dep_graph.clone(),
       css: r.css.clone(),
+      extracted_css: r.extracted_css.clone(),
+      diagnostics: r.diagnostics.clone(),
     })
     .unwrap(),
   )
 }
+
+#[wasm_bindgen(js_name = "formatDiagnostics")]
+pub fn format_diagnostics(
+  specifier: &str,
+  source: &str,
+  diagnostics: JsValue,
+  style: &str,
+) -> Result<String, JsValue> {
+  let diagnostics: DiagnosticBuffer = diagnostics
+    .into_serde()
+    .map_err(|err| format!("failed to parse diagnostics: {}", err))?;
+  let style = match style {
+    "ansi" => FrameStyle::Ansi,
+    "html" => FrameStyle::Html,
+    _ => FrameStyle::Plain,
+  };
+  Ok(render_code_frames(specifier, source, &diagnostics, style))
+}